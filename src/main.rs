@@ -1,7 +1,22 @@
 use std::{cell::RefCell, env, error::Error, rc::Rc, time::Duration};
 
 use wayland_raw::wayland::{
-	Context, IdentManager, RcCell, buffer::Buffer, callback::Callback, compositor::Compositor, display::Display, shm::{PixelFormat, SharedMemory}, wire::MessageManager, xdgshell::{XdgTopLevel, XdgWmBase}
+	Context, IdentManager, WaylandObjectKind,
+	activation::{TOKEN_ENV, XdgActivation},
+	allocator::ShmAllocator,
+	canvas::{Canvas, Color},
+	compositor::Compositor,
+	cursor::CursorSurface,
+	cursor_shape::CursorShape,
+	display::Display,
+	font::Font,
+	region::Region,
+	seat::Seat,
+	shm::{PixelFormat, SharedMemory},
+	swapchain::Swapchain,
+	wire::MessageManager,
+	xcursor::CursorTheme,
+	xdgshell::{XdgTopLevel, XdgWmBase},
 };
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -19,48 +34,91 @@ fn main() -> Result<(), Box<dyn Error>> {
 	let compositor = Compositor::new_bound(&mut registry.borrow_mut(), ctx.clone())?;
 	let surface = compositor.borrow_mut().make_surface()?;
 	let shm = SharedMemory::new_bound_initialized(&mut registry.borrow_mut(), ctx.clone())?;
-	let shm_pool = shm.borrow_mut().make_pool(W * H * 4)?;
+	let allocator = ShmAllocator::new(&mut shm.borrow_mut(), W * H * 4)?;
 	ctx.borrow_mut().handle_events()?;
-	let buf = Buffer::new_initalized(shm_pool.clone(), (0, W, H, W), PixelFormat::Xrgb888, ctx.clone())?;
+	let swapchain = Swapchain::new(
+		allocator.clone(),
+		&mut surface.borrow_mut(),
+		(W, H),
+		PixelFormat::Xrgb8888,
+		2,
+	);
+	let seat = Seat::new_bound(&mut registry.borrow_mut())?;
+	let pointer = seat.borrow().get_pointer()?;
+	let cursor =
+		CursorSurface::new(&compositor.borrow(), allocator.clone(), CursorTheme::from_env())?;
+	pointer.borrow_mut().set_cursor_surface(cursor)?;
+	pointer.borrow_mut().enable_cursor_shape(&mut registry.borrow_mut())?;
+	// no cursor-shape and no xcursor theme just leaves the compositor's cursor
+	if let Err(er) = pointer.borrow_mut().set_cursor_shape(CursorShape::Default) {
+		eprintln!("can't set the cursor: {er}");
+	}
 	let xdg_wm_base = XdgWmBase::new_bound(&mut registry.borrow_mut())?;
 	let xdg_surface = xdg_wm_base.borrow_mut().make_xdg_surface(surface.clone(), (W, H))?;
 	let xdg_toplevel = XdgTopLevel::new_from_xdg_surface(xdg_surface.clone(), ctx.clone())?;
 	xdg_toplevel.borrow_mut().set_app_id(String::from("wayland-raw-appid"))?;
 	xdg_toplevel.borrow_mut().set_title(String::from("wayland-raw-title"))?;
-	surface.borrow_mut().attach_buffer_obj(buf.clone())?;
 	surface.borrow_mut().commit()?;
+	let has_activation =
+		registry.borrow().does_implement(WaylandObjectKind::XdgActivation.as_str()).is_some();
+	let activation = if has_activation {
+		Some(XdgActivation::new_bound(&mut registry.borrow_mut())?)
+	} else {
+		None
+	};
+	let mut activated = false;
+	let font = Font::builtin();
 	let mut frame: usize = 0;
-	let mut cb: Option<RcCell<Callback>> = None;
+
+	surface.borrow_mut().set_redraw(move |surface, _time| {
+		let (r, g, b) = hsv_to_rgb(frame as f64, 1.0, 1.0);
+
+		let buf = swapchain.borrow_mut().next_buffer()?;
+		frame = frame.wrapping_add(1);
+		{
+			let mut buf = buf.borrow_mut();
+			let mut canvas = Canvas::new(buf.map_mut()?)?;
+			canvas.clear(Color::rgb(r, g, b));
+			let (w, h) = (canvas.width, canvas.height);
+			canvas.fill_rounded_rect(
+				Region::new(w / 8, h / 8, w * 3 / 4, h / 4),
+				24,
+				Color::rgba(255, 255, 255, 160),
+			);
+			canvas.line((w / 8, h / 2), (w * 7 / 8, h * 3 / 4), Color::BLACK);
+			let scale = surface.buffer_scale as u32;
+			font.draw(
+				&mut canvas,
+				(w / 8 + 16, h / 8 + 16),
+				"wayland-raw\nhello",
+				Color::BLACK,
+				2 * scale,
+			);
+		}
+		surface.attach_buffer_obj(buf)?;
+		surface.repaint()?;
+		surface.commit()?;
+		// keeps cycling colors
+		surface.mark_dirty();
+		Ok(())
+	});
+	surface.borrow_mut().mark_dirty();
 
 	loop {
 		ctx.borrow_mut().handle_events()?;
+		pointer.borrow_mut().update_cursor()?;
 
 		if xdg_surface.borrow().is_configured {
-			println!("looping");
-			let ready = match &cb.clone() {
-				Some(cb) => cb.borrow().done,
-				None => true,
-			};
-
-			if ready {
-				let new_cb = surface.borrow_mut().frame()?;
-				cb = Some(new_cb);
-
-				let (r, g, b) = hsv_to_rgb(frame as f64, 1.0, 1.0);
-
-				unsafe {
-					let slice = &mut *shm_pool.borrow_mut().slice.unwrap();
-					frame = frame.wrapping_add(1);
-
-					for pixel in slice.chunks_mut(4) {
-						pixel[0] = r;
-						pixel[1] = g;
-						pixel[2] = b;
-					}
+			surface.borrow_mut().update_frame()?;
+			// the first frame mapped the surface
+			if !activated {
+				activated = true;
+				if let Some(activation) = &activation {
+					activation.borrow().activate_from_env(&surface.borrow())?;
+					activation.borrow().destroy()?;
 				}
-				surface.borrow_mut().attach_buffer()?;
-				surface.borrow_mut().repaint()?;
-				surface.borrow_mut().commit()?;
+				// so nothing we spawn reuses it, we're still the only thread
+				unsafe { env::remove_var(TOKEN_ENV) };
 			}
 		} else {
 			std::thread::sleep(Duration::from_millis(100));
@@ -0,0 +1,219 @@
+use std::{cell::RefCell, env, error::Error, rc::Rc};
+
+use crate::wayland::{
+	CtxType, DebugLevel, EventAction, RcCell, WaylandError, WaylandObject, WaylandObjectKind,
+	registry::Registry,
+	seat::Seat,
+	surface::Surface,
+	wire::{FromWirePayload, Id, WireArgument, WireRequest},
+};
+
+// set by launchers for the client they start
+pub const TOKEN_ENV: &str = "XDG_ACTIVATION_TOKEN";
+
+pub struct XdgActivation {
+	pub id: Id,
+	ctx: CtxType,
+}
+
+impl XdgActivation {
+	pub fn new_bound(registry: &mut Registry) -> Result<RcCell<Self>, Box<dyn Error>> {
+		let obj = Rc::new(RefCell::new(Self {
+			id: 0,
+			ctx: registry.ctx.clone(),
+		}));
+		let id = registry
+			.ctx
+			.borrow_mut()
+			.wlim
+			.new_id_registered(WaylandObjectKind::XdgActivation, obj.clone());
+		obj.borrow_mut().id = id;
+		registry.bind(id, WaylandObjectKind::XdgActivation, 1)?;
+		Ok(obj)
+	}
+
+	pub(crate) fn wl_destroy(&self) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 0,
+			args: vec![],
+		})
+	}
+
+	pub fn destroy(&self) -> Result<(), Box<dyn Error>> {
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_destroy()?)?;
+		self.ctx.borrow_mut().wlim.free_id(self.id)?;
+		Ok(())
+	}
+
+	pub(crate) fn wl_get_activation_token(&self, id: Id) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 1,
+			args: vec![WireArgument::NewId(id)],
+		})
+	}
+
+	// the serial of the input event that asked for it, and the surface it happened on,
+	// make the compositor more likely to honor the token. app_id is of the client to be activated
+	pub fn request_token(
+		&self,
+		serial: Option<(u32, &Seat)>,
+		surface: Option<&Surface>,
+		app_id: Option<&str>,
+	) -> Result<RcCell<XdgActivationToken>, Box<dyn Error>> {
+		let obj = Rc::new(RefCell::new(XdgActivationToken {
+			id: 0,
+			token: None,
+		}));
+		let id = self
+			.ctx
+			.borrow_mut()
+			.wlim
+			.new_id_registered(WaylandObjectKind::XdgActivationToken, obj.clone());
+		obj.borrow_mut().id = id;
+		let ctx = self.ctx.borrow();
+		ctx.wlmm.send_request(&mut self.wl_get_activation_token(id)?)?;
+		let token = obj.borrow();
+		if let Some((serial, seat)) = serial {
+			ctx.wlmm.send_request(&mut token.wl_set_serial(serial, seat.id)?)?;
+		}
+		if let Some(app_id) = app_id {
+			ctx.wlmm.send_request(&mut token.wl_set_app_id(app_id)?)?;
+		}
+		if let Some(surface) = surface {
+			ctx.wlmm.send_request(&mut token.wl_set_surface(surface.id)?)?;
+		}
+		ctx.wlmm.send_request(&mut token.wl_commit()?)?;
+		drop(token);
+		Ok(obj)
+	}
+
+	pub(crate) fn wl_activate(
+		&self,
+		token: &str,
+		surface_id: Id,
+	) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 2,
+			args: vec![WireArgument::String(token.to_string()), WireArgument::Obj(surface_id)],
+		})
+	}
+
+	// the compositor may still just mark the surface as urgent instead
+	pub fn activate(&self, token: &str, surface: &Surface) -> Result<(), Box<dyn Error>> {
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_activate(token, surface.id)?)
+	}
+
+	// with the token we were started with, if any. call once the surface is mapped
+	pub fn activate_from_env(&self, surface: &Surface) -> Result<bool, Box<dyn Error>> {
+		let Some(token) = token_from_env() else {
+			return Ok(false);
+		};
+		self.activate(&token, surface)?;
+		Ok(true)
+	}
+}
+
+// leaves the environment alone, remove TOKEN_ENV once activated so processes we spawn
+// don't reuse it. that's unsafe once other threads exist, so it's up to the caller
+pub fn token_from_env() -> Option<String> {
+	env::var(TOKEN_ENV).ok().filter(|token| !token.is_empty())
+}
+
+// single use, pass it on through XDG_ACTIVATION_TOKEN or to another of our surfaces
+pub struct XdgActivationToken {
+	pub id: Id,
+	// sent by done, the object is destroyed by then
+	pub token: Option<String>,
+}
+
+impl XdgActivationToken {
+	pub(crate) fn wl_set_serial(
+		&self,
+		serial: u32,
+		seat_id: Id,
+	) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 0,
+			args: vec![WireArgument::UnInt(serial), WireArgument::Obj(seat_id)],
+		})
+	}
+
+	pub(crate) fn wl_set_app_id(&self, app_id: &str) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 1,
+			args: vec![WireArgument::String(app_id.to_string())],
+		})
+	}
+
+	pub(crate) fn wl_set_surface(&self, surface_id: Id) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 2,
+			args: vec![WireArgument::Obj(surface_id)],
+		})
+	}
+
+	pub(crate) fn wl_commit(&self) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 3,
+			args: vec![],
+		})
+	}
+
+	pub(crate) fn wl_destroy(&self) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 4,
+			args: vec![],
+		})
+	}
+}
+
+impl WaylandObject for XdgActivation {
+	fn handle(
+		&mut self,
+		opcode: super::OpCode,
+		_payload: &[u8],
+	) -> Result<Vec<EventAction>, Box<dyn Error>> {
+		Err(WaylandError::InvalidOpCode(opcode, self.as_str()).boxed())
+	}
+
+	fn as_str(&self) -> &'static str {
+		WaylandObjectKind::XdgActivation.as_str()
+	}
+}
+
+impl WaylandObject for XdgActivationToken {
+	fn handle(
+		&mut self,
+		opcode: super::OpCode,
+		payload: &[u8],
+	) -> Result<Vec<EventAction>, Box<dyn Error>> {
+		let mut pending = vec![];
+		match opcode {
+			// done
+			0 => {
+				let token = String::from_wire(payload)?;
+				pending.push(EventAction::DebugMessage(
+					DebugLevel::Verbose,
+					format!("{} {} done // {}", self.as_str(), self.id, token),
+				));
+				self.token = Some(token);
+				pending.push(EventAction::Request(self.wl_destroy()?));
+				pending.push(EventAction::IdDeletion(self.id));
+			}
+			inv => return Err(WaylandError::InvalidOpCode(inv, self.as_str()).boxed()),
+		}
+		Ok(pending)
+	}
+
+	fn as_str(&self) -> &'static str {
+		WaylandObjectKind::XdgActivationToken.as_str()
+	}
+}
@@ -0,0 +1,93 @@
+use std::{cell::RefCell, error::Error, rc::Rc};
+
+use crate::wayland::{
+	Context, CtxType, RcCell, WaylandError,
+	shm::{SharedMemory, SharedMemoryPool},
+};
+
+// every range starts on this, keeps rows of any format aligned
+const ALIGN: i32 = 64;
+
+fn align_up(n: i32, to: i32) -> i32 {
+	(n + to - 1) / to * to
+}
+
+// stride of a tightly packed row, padded to a whole u32
+pub fn stride_for(width: i32, bits_per_pixel: i32) -> i32 {
+	align_up((width * bits_per_pixel + 7) / 8, 4)
+}
+
+// first fit allocator carving buffers out of one pool, see Buffer::new_allocated
+pub struct ShmAllocator {
+	pub(crate) ctx: CtxType,
+	pub(crate) pool: RcCell<SharedMemoryPool>,
+	// (offset, len), sorted by offset and never touching each other
+	free: Vec<(i32, i32)>,
+}
+
+impl ShmAllocator {
+	pub fn new(shm: &mut SharedMemory, size: i32) -> Result<RcCell<Self>, Box<dyn Error>> {
+		let size = align_up(size.max(1), ALIGN);
+		let pool = shm.make_pool(size)?;
+		Ok(Rc::new(RefCell::new(Self {
+			ctx: shm.ctx.clone(),
+			pool,
+			free: vec![(0, size)],
+		})))
+	}
+
+	pub fn alloc(&mut self, len: i32) -> Result<i32, Box<dyn Error>> {
+		let ctx = self.ctx.clone();
+		self.alloc_in(&ctx.borrow(), len)
+	}
+
+	// returns the offset of a range of at least len bytes, growing the pool if nothing fits
+	pub(crate) fn alloc_in(&mut self, ctx: &Context, len: i32) -> Result<i32, Box<dyn Error>> {
+		let len = align_up(len.max(1), ALIGN);
+		if let Some(offset) = self.take(len) {
+			return Ok(offset);
+		}
+
+		let old_size = self.pool.borrow().size;
+		// doubling keeps the number of remaps down when lots of small buffers come in
+		let new_size = (old_size * 2).max(old_size + len);
+		self.pool
+			.try_borrow_mut()
+			.map_err(|_| WaylandError::PoolMapped)?
+			.resize_in(ctx, new_size)?;
+		self.free(old_size, new_size - old_size);
+		self.alloc_in(ctx, len)
+	}
+
+	fn take(&mut self, len: i32) -> Option<i32> {
+		let ix = self.free.iter().position(|(_, free_len)| *free_len >= len)?;
+		let (offset, free_len) = self.free[ix];
+		if free_len == len {
+			self.free.remove(ix);
+		} else {
+			self.free[ix] = (offset + len, free_len - len);
+		}
+		Some(offset)
+	}
+
+	// only for ranges no buffer uses anymore, see Buffer::destroy
+	pub(crate) fn free(&mut self, offset: i32, len: i32) {
+		let len = align_up(len.max(1), ALIGN);
+		let ix = self.free.partition_point(|(free_offset, _)| *free_offset < offset);
+		self.free.insert(ix, (offset, len));
+
+		// merge with the next range, then with the previous one
+		if ix + 1 < self.free.len() && offset + len == self.free[ix + 1].0 {
+			self.free[ix].1 += self.free[ix + 1].1;
+			self.free.remove(ix + 1);
+		}
+		if ix > 0 && self.free[ix - 1].0 + self.free[ix - 1].1 == offset {
+			self.free[ix - 1].1 += self.free[ix].1;
+			self.free.remove(ix);
+		}
+	}
+
+	pub fn free_bytes(&self) -> i32 {
+		self.free.iter().map(|(_, len)| len).sum()
+	}
+}
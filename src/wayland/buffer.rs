@@ -1,31 +1,53 @@
-use std::{cell::RefCell, error::Error, rc::Rc};
+use std::{
+	cell::{Ref, RefCell},
+	error::Error,
+	rc::Rc,
+};
 
 use crate::{
 	drop,
 	wayland::{
-		CtxType, DebugLevel, EventAction, OpCode, RcCell, WaylandError, WaylandObject, WaylandObjectKind, shm::{PixelFormat, SharedMemoryPool}, wire::{Id, WireRequest}
+		Context, CtxType, DebugLevel, EventAction, OpCode, RcCell, WaylandError, WaylandObject,
+		WaylandObjectKind,
+		allocator::{ShmAllocator, stride_for},
+		canvas::Pixmap,
+		image,
+		shm::{PixelFormat, SharedMemory, SharedMemoryPool},
+		wire::{Id, WireRequest},
 	},
 };
 
 pub struct Buffer {
 	pub id: Id,
 	pub(crate) ctx: CtxType,
-	pub offset: i32,
-	pub width: i32,
-	pub height: i32,
-	pub stride: i32,
+	// read only outside the crate, map_mut trusts the range to be ours alone
+	pub(crate) offset: i32,
+	pub(crate) width: i32,
+	pub(crate) height: i32,
+	pub(crate) stride: i32,
 	pub format: PixelFormat,
 	pub in_use: bool,
-	pub shm_pool: RcCell<SharedMemoryPool>,
+	// frames since its contents were last shown, 0 when they are undefined
+	pub age: u32,
+	pub(crate) shm_pool: RcCell<SharedMemoryPool>,
+	// set when the range came from an allocator and has to go back to it
+	pub(crate) allocator: Option<RcCell<ShmAllocator>>,
+	// so dropping it afterwards doesn't destroy it twice
+	destroyed: bool,
 }
 
 impl Buffer {
-	pub fn new_initalized(
+	// the range must not overlap any other buffer's, map_mut relies on it.
+	// outside the crate buffers come from an allocator or a pool of their own
+	pub(crate) fn new_initalized(
 		shmp: RcCell<SharedMemoryPool>,
 		(offset, width, height, stride): (i32, i32, i32, i32),
 		format: PixelFormat,
 		ctx: CtxType,
 	) -> Result<RcCell<Buffer>, Box<dyn Error>> {
+		if !shmp.borrow().supports(format) {
+			return Err(WaylandError::UnsupportedPixelFormat(format).boxed());
+		}
 		let buf = Rc::new(RefCell::new(Buffer {
 			id: 0,
 			ctx: ctx.clone(),
@@ -35,34 +57,207 @@ impl Buffer {
 			stride,
 			format,
 			in_use: false,
+			age: 0,
 			shm_pool: shmp.clone(),
+			allocator: None,
+			destroyed: false,
 		}));
 		let id = ctx.borrow_mut().wlim.new_id_registered(WaylandObjectKind::Buffer, buf.clone());
 		buf.borrow_mut().id = id;
-		shmp.borrow().wl_create_buffer(id, (offset, width, height, stride), format)?;
+		let mut req =
+			shmp.borrow().wl_create_buffer(id, (offset, width, height, stride), format)?;
+		ctx.borrow().wlmm.send_request(&mut req)?;
+		Ok(buf)
+	}
+
+	pub fn offset(&self) -> i32 {
+		self.offset
+	}
+
+	pub fn width(&self) -> i32 {
+		self.width
+	}
+
+	pub fn height(&self) -> i32 {
+		self.height
+	}
+
+	// in bytes
+	pub fn stride(&self) -> i32 {
+		self.stride
+	}
+
+	// takes its range from the allocator, which gets it back on destroy
+	pub fn new_allocated(
+		allocator: &RcCell<ShmAllocator>,
+		(width, height): (i32, i32),
+		format: PixelFormat,
+	) -> Result<RcCell<Buffer>, Box<dyn Error>> {
+		let stride = stride_for(width, format.bits_per_pixel() as i32);
+		let (offset, pool, ctx) = {
+			let mut alloc = allocator.borrow_mut();
+			(alloc.alloc(stride * height)?, alloc.pool.clone(), alloc.ctx.clone())
+		};
+		let buf = Self::new_initalized(pool, (offset, width, height, stride), format, ctx)?;
+		buf.borrow_mut().allocator = Some(allocator.clone());
 		Ok(buf)
 	}
 
-	pub(crate) fn wl_destroy(&self) -> Result<(), Box<dyn Error>> {
-		self.ctx.borrow().wlmm.send_request(&mut WireRequest {
+	// uploads a decoded image into a pool of its own
+	pub fn from_image(
+		shm: &mut SharedMemory,
+		image: &Pixmap,
+		format: PixelFormat,
+	) -> Result<RcCell<Buffer>, Box<dyn Error>> {
+		let bytes = image::encode(image, format)?;
+		let (width, height) = (image.width as i32, image.height as i32);
+		let stride = stride_for(width, format.bits_per_pixel() as i32);
+		let pool = shm.make_pool(stride * height)?;
+		let buf = Self::new_initalized(pool, (0, width, height, stride), format, shm.ctx.clone())?;
+		buf.borrow_mut().map_mut()?.bytes().copy_from_slice(&bytes);
+		Ok(buf)
+	}
+
+	pub(crate) fn wl_destroy(&self) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
 			sender_id: self.id,
 			opcode: 0,
 			args: vec![],
 		})
 	}
 
-	pub fn destroy(&self) -> Result<(), Box<dyn Error>> {
-		self.wl_destroy()?;
+	pub fn destroy(&mut self) -> Result<(), Box<dyn Error>> {
+		if self.destroyed {
+			return Ok(());
+		}
+		self.destroyed = true;
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_destroy()?)?;
 		self.ctx.borrow_mut().wlim.free_id(self.id)?;
+		if let Some(alloc) = &self.allocator {
+			alloc.borrow_mut().free(self.offset, self.stride * self.height);
+		}
 		Ok(())
 	}
 
-	pub fn resize(&mut self, (w, h): (i32, i32)) -> Result<(), Box<dyn Error>> {
+	// the buffer gets a new id, so it has to be attached again afterwards
+	pub fn resize(&mut self, size: (i32, i32)) -> Result<(), Box<dyn Error>> {
+		let ctx = self.ctx.clone();
+		self.resize_in(&mut ctx.borrow_mut(), size)
+	}
+
+	// wl_buffer geometry is fixed, so the old one goes and a new one takes its place.
+	// the pool only grows, a smaller buffer just uses less of it
+	pub(crate) fn resize_in(
+		&mut self,
+		ctx: &mut Context,
+		(w, h): (i32, i32),
+	) -> Result<(), Box<dyn Error>> {
+		let stride = stride_for(w, self.format.bits_per_pixel() as i32);
+		let old_range = (self.offset, self.stride * self.height);
+		if let Some(alloc) = &self.allocator {
+			let mut alloc = alloc.borrow_mut();
+			// the old range stays ours if there's no room for the new one
+			let offset = alloc.alloc_in(ctx, stride * h)?;
+			if !self.in_use {
+				alloc.free(old_range.0, old_range.1);
+			}
+			self.offset = offset;
+		} else {
+			self.shm_pool
+				.try_borrow_mut()
+				.map_err(|_| WaylandError::PoolMapped)?
+				.resize_in(ctx, self.offset + stride * h)?;
+		}
+		let shmp = self.shm_pool.borrow();
+
+		let obj = ctx.wlim.find_obj_by_id(self.id)?.1.clone();
+		if self.in_use {
+			// the compositor may still read the old one, it goes once released
+			let old = Rc::new(RefCell::new(RetiredBuffer {
+				id: self.id,
+				allocator: self.allocator.clone(),
+				range: old_range,
+			}));
+			ctx.wlim.register(self.id, WaylandObjectKind::Buffer, old);
+		} else {
+			ctx.wlmm.send_request(&mut self.wl_destroy()?)?;
+			ctx.wlim.free_id(self.id)?;
+		}
+		let id = ctx.wlim.new_id_registered(WaylandObjectKind::Buffer, obj);
+
+		self.id = id;
 		self.width = w;
 		self.height = h;
-		let shmp = self.shm_pool.clone();
-		let mut shmp = shmp.borrow_mut();
-		shmp.resize(w * h * self.format.width() as i32)
+		self.stride = stride;
+		self.in_use = false;
+		self.age = 0;
+		ctx.wlmm.send_request(&mut shmp.wl_create_buffer(
+			id,
+			(self.offset, w, h, stride),
+			self.format,
+		)?)
+	}
+
+	// the pool can't be remapped and the buffer can't be resized while the map lives
+	pub fn map_mut(&mut self) -> Result<BufferMap<'_>, Box<dyn Error>> {
+		let (offset, len) = (self.offset as usize, (self.stride * self.height) as usize);
+		let pool = self.shm_pool.try_borrow().map_err(|_| WaylandError::PoolMapped)?;
+		let slice = pool.slice.ok_or(WaylandError::ObjectNonExistent)?;
+		if offset + len > slice.len() {
+			return Err(WaylandError::ObjectNonExistent.boxed());
+		}
+		// buffer ranges never overlap, they come from the allocator or a pool of their own.
+		// so the region is only ever handed out through the &mut self we are holding
+		let bytes = unsafe { &mut (&mut *slice)[offset..offset + len] };
+		Ok(BufferMap {
+			_pool: pool,
+			bytes,
+			width: self.width as usize,
+			height: self.height as usize,
+			stride: self.stride as usize,
+			format: self.format,
+		})
+	}
+}
+
+// the wl_buffer a resize replaced while the compositor still had it
+struct RetiredBuffer {
+	id: Id,
+	allocator: Option<RcCell<ShmAllocator>>,
+	// (offset, len) in the pool, only returned to an allocator
+	range: (i32, i32),
+}
+
+pub struct BufferMap<'a> {
+	_pool: Ref<'a, SharedMemoryPool>,
+	bytes: &'a mut [u8],
+	pub width: usize,
+	pub height: usize,
+	// in bytes
+	pub stride: usize,
+	pub format: PixelFormat,
+}
+
+impl BufferMap<'_> {
+	pub fn bytes(&mut self) -> &mut [u8] {
+		self.bytes
+	}
+
+	// padding at the end of rows included, see rows
+	pub fn pixels(&mut self) -> Result<&mut [u32], WaylandError> {
+		if self.format.bits_per_pixel() != 32 || !self.stride.is_multiple_of(4) {
+			return Err(WaylandError::InvalidPixelFormat);
+		}
+		let (prefix, pixels, _) = unsafe { self.bytes.align_to_mut::<u32>() };
+		if !prefix.is_empty() {
+			return Err(WaylandError::UnalignedPixels);
+		}
+		Ok(pixels)
+	}
+
+	pub fn rows(&mut self) -> Result<impl Iterator<Item = &mut [u32]>, WaylandError> {
+		let (width, stride) = (self.width, self.stride / 4);
+		Ok(self.pixels()?.chunks_exact_mut(stride).map(move |row| &mut row[..width]))
 	}
 }
 
@@ -92,4 +287,34 @@ impl WaylandObject for Buffer {
 	}
 }
 
+impl WaylandObject for RetiredBuffer {
+	fn handle(
+		&mut self,
+		opcode: super::OpCode,
+		_payload: &[u8],
+	) -> Result<Vec<EventAction>, Box<dyn Error>> {
+		match opcode {
+			// release
+			0 => {
+				if let Some(alloc) = &self.allocator {
+					alloc.borrow_mut().free(self.range.0, self.range.1);
+				}
+				Ok(vec![
+					EventAction::Request(WireRequest {
+						sender_id: self.id,
+						opcode: 0,
+						args: vec![],
+					}),
+					EventAction::IdDeletion(self.id),
+				])
+			}
+			inv => Err(WaylandError::InvalidOpCode(inv as OpCode, self.as_str()).boxed()),
+		}
+	}
+
+	fn as_str(&self) -> &'static str {
+		WaylandObjectKind::Buffer.as_str()
+	}
+}
+
 drop!(Buffer);
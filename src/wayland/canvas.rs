@@ -0,0 +1,407 @@
+use crate::wayland::{WaylandError, buffer::BufferMap, region::Region, shm::PixelFormat};
+
+// straight alpha, premultiplied on the way into the buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color {
+	pub r: u8,
+	pub g: u8,
+	pub b: u8,
+	pub a: u8,
+}
+
+impl Color {
+	pub const BLACK: Color = Color::rgb(0, 0, 0);
+	pub const WHITE: Color = Color::rgb(255, 255, 255);
+	pub const TRANSPARENT: Color = Color::rgba(0, 0, 0, 0);
+
+	pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+		Self::rgba(r, g, b, 255)
+	}
+
+	pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+		Self {
+			r,
+			g,
+			b,
+			a,
+		}
+	}
+
+	// 0xaarrggbb
+	pub const fn from_argb(argb: u32) -> Self {
+		Self::rgba((argb >> 16) as u8, (argb >> 8) as u8, argb as u8, (argb >> 24) as u8)
+	}
+
+	pub fn premultiplied(&self) -> u32 {
+		let a = self.a as u32;
+		a << 24 | mul(self.r as u32, a) << 16 | mul(self.g as u32, a) << 8 | mul(self.b as u32, a)
+	}
+}
+
+// premultiplied 0xaarrggbb, the same layout xcursor images come in
+#[derive(Debug, Clone)]
+pub struct Pixmap {
+	pub width: u32,
+	pub height: u32,
+	pub pixels: Vec<u32>,
+}
+
+impl Pixmap {
+	pub fn new(width: u32, height: u32) -> Self {
+		Self {
+			width,
+			height,
+			pixels: vec![0; (width * height) as usize],
+		}
+	}
+}
+
+fn mul(c: u32, a: u32) -> u32 {
+	(c * a + 127) / 255
+}
+
+fn scale(px: u32, coverage: u32) -> u32 {
+	mul(px >> 24, coverage) << 24
+		| mul((px >> 16) & 0xff, coverage) << 16
+		| mul((px >> 8) & 0xff, coverage) << 8
+		| mul(px & 0xff, coverage)
+}
+
+// premultiplied src over dst
+fn over(src: u32, dst: u32) -> u32 {
+	let inv = 255 - (src >> 24);
+	match inv {
+		0 => src,
+		255 => dst,
+		_ => {
+			let ch = |shift: u32| {
+				(((src >> shift) & 0xff) + mul((dst >> shift) & 0xff, inv)).min(255) << shift
+			};
+			ch(24) | ch(16) | ch(8) | ch(0)
+		}
+	}
+}
+
+fn swap_rb(argb: u32) -> u32 {
+	argb & 0xff00ff00 | (argb >> 16) & 0xff | (argb & 0xff) << 16
+}
+
+fn widen(v: u32, bits: u32) -> u32 {
+	let max = (1 << bits) - 1;
+	(v & max) * 255 / max
+}
+
+fn narrow(v: u32, bits: u32) -> u32 {
+	(v * ((1 << bits) - 1) + 127) / 255
+}
+
+fn widen_10(v: u32) -> u32 {
+	v << 2 | v >> 6
+}
+
+pub(crate) fn drawable(format: PixelFormat) -> bool {
+	use PixelFormat::*;
+	matches!(
+		format,
+		Argb8888
+			| Xrgb8888
+			| Abgr8888
+			| Xbgr8888
+			| Rgba8888
+			| Rgbx8888
+			| Bgra8888
+			| Bgrx8888
+			| Rgb888 | Bgr888
+			| Rgb565 | Bgr565
+			| Argb2101010
+			| Xrgb2101010
+			| Abgr2101010
+			| Xbgr2101010
+	)
+}
+
+// buffer bytes to premultiplied argb, formats without alpha come out opaque
+fn decode(format: PixelFormat, px: &[u8]) -> u32 {
+	use PixelFormat::*;
+	let word = || u32::from_le_bytes([px[0], px[1], px[2], px[3]]);
+	let half = || u16::from_le_bytes([px[0], px[1]]) as u32;
+	let opaque = 0xff000000;
+	let from_1010102 = |v: u32| {
+		widen(v >> 30, 2) << 24
+			| (((v >> 20) & 0x3ff) >> 2) << 16
+			| (((v >> 10) & 0x3ff) >> 2) << 8
+			| (v & 0x3ff) >> 2
+	};
+	let from_565 = |v: u32| opaque | widen(v >> 11, 5) << 16 | widen(v >> 5, 6) << 8 | widen(v, 5);
+	match format {
+		Argb8888 => word(),
+		Xrgb8888 => word() | opaque,
+		Abgr8888 => swap_rb(word()),
+		Xbgr8888 => swap_rb(word()) | opaque,
+		Rgba8888 => word().rotate_right(8),
+		Rgbx8888 => word().rotate_right(8) | opaque,
+		Bgra8888 => swap_rb(word().rotate_right(8)),
+		Bgrx8888 => swap_rb(word().rotate_right(8)) | opaque,
+		Rgb888 => opaque | (px[2] as u32) << 16 | (px[1] as u32) << 8 | px[0] as u32,
+		Bgr888 => opaque | (px[0] as u32) << 16 | (px[1] as u32) << 8 | px[2] as u32,
+		Rgb565 => from_565(half()),
+		Bgr565 => swap_rb(from_565(half())),
+		Argb2101010 => from_1010102(word()),
+		Xrgb2101010 => from_1010102(word()) | opaque,
+		Abgr2101010 => swap_rb(from_1010102(word())),
+		Xbgr2101010 => swap_rb(from_1010102(word())) | opaque,
+		_ => 0,
+	}
+}
+
+pub(crate) fn encode(format: PixelFormat, argb: u32, px: &mut [u8]) {
+	use PixelFormat::*;
+	let to_1010102 = |v: u32| {
+		narrow(v >> 24, 2) << 30
+			| widen_10((v >> 16) & 0xff) << 20
+			| widen_10((v >> 8) & 0xff) << 10
+			| widen_10(v & 0xff)
+	};
+	let to_565 = |v: u32| {
+		narrow((v >> 16) & 0xff, 5) << 11 | narrow((v >> 8) & 0xff, 6) << 5 | narrow(v & 0xff, 5)
+	};
+	let word = match format {
+		Argb8888 | Xrgb8888 => argb,
+		Abgr8888 | Xbgr8888 => swap_rb(argb),
+		Rgba8888 | Rgbx8888 => argb.rotate_left(8),
+		Bgra8888 | Bgrx8888 => swap_rb(argb).rotate_left(8),
+		Argb2101010 | Xrgb2101010 => to_1010102(argb),
+		Abgr2101010 | Xbgr2101010 => to_1010102(swap_rb(argb)),
+		Rgb888 => {
+			px[..3].copy_from_slice(&argb.to_le_bytes()[..3]);
+			return;
+		}
+		Bgr888 => {
+			px[..3].copy_from_slice(&swap_rb(argb).to_le_bytes()[..3]);
+			return;
+		}
+		Rgb565 => {
+			px[..2].copy_from_slice(&(to_565(argb) as u16).to_le_bytes());
+			return;
+		}
+		Bgr565 => {
+			px[..2].copy_from_slice(&(to_565(swap_rb(argb)) as u16).to_le_bytes());
+			return;
+		}
+		_ => return,
+	};
+	px[..4].copy_from_slice(&word.to_le_bytes());
+}
+
+// software drawing into a mapped buffer, everything gets clipped to the clip region.
+// argb8888 and xrgb8888 write whole u32s, the rest goes through decode/encode
+pub struct Canvas<'a> {
+	map: BufferMap<'a>,
+	pub width: i32,
+	pub height: i32,
+	clip: Region,
+	fast: bool,
+}
+
+impl<'a> Canvas<'a> {
+	pub fn new(mut map: BufferMap<'a>) -> Result<Self, WaylandError> {
+		if !drawable(map.format) {
+			return Err(WaylandError::UndrawableFormat(map.format));
+		}
+		let fast = matches!(map.format, PixelFormat::Argb8888 | PixelFormat::Xrgb8888)
+			&& map.pixels().is_ok();
+		let (width, height) = (map.width as i32, map.height as i32);
+		Ok(Self {
+			map,
+			width,
+			height,
+			clip: Region::new(0, 0, width, height),
+			fast,
+		})
+	}
+
+	pub fn clip(&self) -> Region {
+		self.clip
+	}
+
+	pub fn set_clip(&mut self, clip: Region) {
+		self.clip = clip
+			.intersect(Region::new(0, 0, self.width, self.height))
+			.unwrap_or(Region::new(0, 0, 0, 0));
+	}
+
+	pub fn reset_clip(&mut self) {
+		self.clip = Region::new(0, 0, self.width, self.height);
+	}
+
+	// clips x0..x1 on row y, None if nothing is left
+	fn clip_span(&self, y: i32, x0: i32, x1: i32) -> Option<(usize, usize)> {
+		let c = self.clip;
+		if y < c.y || y >= c.y + c.h {
+			return None;
+		}
+		let (x0, x1) = (x0.max(c.x), x1.min(c.x + c.w));
+		(x0 < x1).then_some((x0 as usize, x1 as usize))
+	}
+
+	// src is premultiplied, replace skips blending
+	fn span(&mut self, y: i32, x0: i32, x1: i32, src: u32, replace: bool) {
+		let Some((x0, x1)) = self.clip_span(y, x0, x1) else {
+			return;
+		};
+		let format = self.map.format;
+		let stride = self.map.stride;
+		if self.fast
+			&& let Ok(pixels) = self.map.pixels()
+		{
+			let row = &mut pixels[y as usize * stride / 4..][x0..x1];
+			if replace || src >> 24 == 255 {
+				row.fill(src);
+			} else {
+				row.iter_mut().for_each(|dst| *dst = over(src, *dst));
+			}
+			return;
+		}
+
+		let bpp = format.bytes_per_pixel();
+		let row = &mut self.map.bytes()[y as usize * stride..];
+		for px in row[x0 * bpp..x1 * bpp].chunks_exact_mut(bpp) {
+			let out = if replace {
+				src
+			} else {
+				over(src, decode(format, px))
+			};
+			encode(format, out, px);
+		}
+	}
+
+	fn blit_row(&mut self, y: i32, x: i32, src: &[u32]) {
+		let Some((x0, x1)) = self.clip_span(y, x, x + src.len() as i32) else {
+			return;
+		};
+		let skip = (x0 as i32 - x) as usize;
+		let src = &src[skip..skip + (x1 - x0)];
+		let format = self.map.format;
+		let stride = self.map.stride;
+		if self.fast
+			&& let Ok(pixels) = self.map.pixels()
+		{
+			let row = &mut pixels[y as usize * stride / 4..][x0..x1];
+			row.iter_mut().zip(src).for_each(|(dst, src)| *dst = over(*src, *dst));
+			return;
+		}
+
+		let bpp = format.bytes_per_pixel();
+		let row = &mut self.map.bytes()[y as usize * stride..];
+		for (px, src) in row[x0 * bpp..x1 * bpp].chunks_exact_mut(bpp).zip(src) {
+			encode(format, over(*src, decode(format, px)), px);
+		}
+	}
+
+	// sets every pixel in the clip region, no blending
+	pub fn clear(&mut self, color: Color) {
+		let c = self.clip;
+		let src = color.premultiplied();
+		for y in c.y..c.y + c.h {
+			self.span(y, c.x, c.x + c.w, src, true);
+		}
+	}
+
+	pub fn fill_rect(&mut self, rect: Region, color: Color) {
+		let src = color.premultiplied();
+		for y in rect.y..rect.y + rect.h {
+			self.span(y, rect.x, rect.x + rect.w, src, false);
+		}
+	}
+
+	// the outline stays inside the rect
+	pub fn stroke_rect(&mut self, rect: Region, width: i32, color: Color) {
+		let width = width.min(rect.w / 2).min(rect.h / 2).max(1);
+		let Region {
+			x,
+			y,
+			w,
+			h,
+		} = rect;
+		self.fill_rect(Region::new(x, y, w, width), color);
+		self.fill_rect(Region::new(x, y + h - width, w, width), color);
+		self.fill_rect(Region::new(x, y + width, width, h - 2 * width), color);
+		self.fill_rect(Region::new(x + w - width, y + width, width, h - 2 * width), color);
+	}
+
+	pub fn pixel(&mut self, (x, y): (i32, i32), color: Color) {
+		self.span(y, x, x + 1, color.premultiplied(), false);
+	}
+
+	// bresenham, both ends included
+	pub fn line(&mut self, (x0, y0): (i32, i32), (x1, y1): (i32, i32), color: Color) {
+		let src = color.premultiplied();
+		let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+		let (sx, sy) = (
+			if x0 < x1 {
+				1
+			} else {
+				-1
+			},
+			if y0 < y1 {
+				1
+			} else {
+				-1
+			},
+		);
+		let (mut x, mut y, mut err) = (x0, y0, dx + dy);
+		loop {
+			self.span(y, x, x + 1, src, false);
+			if x == x1 && y == y1 {
+				break;
+			}
+			let e2 = 2 * err;
+			if e2 >= dy {
+				err += dy;
+				x += sx;
+			}
+			if e2 <= dx {
+				err += dx;
+				y += sy;
+			}
+		}
+	}
+
+	// corners get antialiased by how much of each pixel the circle covers
+	pub fn fill_rounded_rect(&mut self, rect: Region, radius: i32, color: Color) {
+		let r = radius.clamp(0, rect.w.min(rect.h) / 2);
+		let src = color.premultiplied();
+		for row in 0..rect.h {
+			let y = rect.y + row;
+			let center_y = match row {
+				row if row < r => r,
+				row if row >= rect.h - r => rect.h - r,
+				_ => {
+					self.span(y, rect.x, rect.x + rect.w, src, false);
+					continue;
+				}
+			};
+			let dy = center_y as f32 - (row as f32 + 0.5);
+			for col in 0..r {
+				let dx = r as f32 - (col as f32 + 0.5);
+				let dist = (dx * dx + dy * dy).sqrt();
+				let coverage = (r as f32 - dist + 0.5).clamp(0.0, 1.0);
+				let px = scale(src, (coverage * 255.0) as u32);
+				self.span(y, rect.x + col, rect.x + col + 1, px, false);
+				let right = rect.x + rect.w - 1 - col;
+				self.span(y, right, right + 1, px, false);
+			}
+			self.span(y, rect.x + r, rect.x + rect.w - r, src, false);
+		}
+	}
+
+	// alpha blended, top left corner at pos
+	pub fn blit(&mut self, (x, y): (i32, i32), src: &Pixmap) {
+		if src.width == 0 {
+			return;
+		}
+		for (row, pixels) in src.pixels.chunks_exact(src.width as usize).enumerate() {
+			self.blit_row(y + row as i32, x, pixels);
+		}
+	}
+}
@@ -1,7 +1,8 @@
 use std::{cell::RefCell, error::Error, rc::Rc};
 
 use crate::wayland::{
-	CtxType, EventAction, RcCell, WaylandObject, WaylandObjectKind,
+	CtxType, EventAction, RcCell, WaylandError, WaylandObject, WaylandObjectKind,
+	region::WlRegion,
 	registry::Registry,
 	surface::Surface,
 	wire::{Id, WireArgument, WireRequest},
@@ -30,7 +31,12 @@ impl Compositor {
 			.wlim
 			.new_id_registered(WaylandObjectKind::Compositor, compositor.clone());
 		compositor.borrow_mut().id = id;
-		registry.bind(id, WaylandObjectKind::Compositor, 5)?;
+		// v6 for wl_surface.preferred_buffer_scale/transform
+		let version = registry
+			.does_implement(WaylandObjectKind::Compositor.as_str())
+			.ok_or(WaylandError::NotInRegistry)?
+			.min(6);
+		registry.bind(id, WaylandObjectKind::Compositor, version)?;
 		Ok(compositor)
 	}
 
@@ -50,16 +56,34 @@ impl Compositor {
 			.wlim
 			.new_id_registered(WaylandObjectKind::Surface, surface.clone());
 		surface.borrow_mut().id = id;
+		self.ctx.borrow_mut().surfaces.push(Rc::downgrade(&surface));
 		self.wl_create_surface(id)?;
 		Ok(surface)
 	}
+
+	pub(crate) fn wl_create_region(&self, id: Id) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 1,
+			args: vec![WireArgument::NewId(id)],
+		})
+	}
+
+	pub fn create_region(&self) -> Result<RcCell<WlRegion>, Box<dyn Error>> {
+		let region = Rc::new(RefCell::new(WlRegion::new(0, self.ctx.clone())));
+		let id =
+			self.ctx.borrow_mut().wlim.new_id_registered(WaylandObjectKind::Region, region.clone());
+		region.borrow_mut().id = id;
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_create_region(id)?)?;
+		Ok(region)
+	}
 }
 
 impl WaylandObject for Compositor {
 	fn handle(
 		&mut self,
-		opcode: super::OpCode,
-		payload: &[u8],
+		_opcode: super::OpCode,
+		_payload: &[u8],
 	) -> Result<Vec<EventAction>, Box<dyn Error>> {
 		todo!()
 	}
@@ -0,0 +1,158 @@
+use std::{cell::RefCell, error::Error, rc::Rc};
+
+use crate::wayland::{
+	RcCell, WaylandError,
+	allocator::ShmAllocator,
+	buffer::Buffer,
+	callback::Callback,
+	compositor::Compositor,
+	region::Region,
+	shm::PixelFormat,
+	surface::Surface,
+	wire::Id,
+	xcursor::{Cursor, CursorTheme},
+};
+
+struct CursorFrame {
+	buffer: RcCell<Buffer>,
+	hotspot: (i32, i32),
+	delay: u32,
+}
+
+// a wl_surface showing every frame of the current cursor, each in its own buffer
+pub struct CursorSurface {
+	pub theme: CursorTheme,
+	pub name: Option<String>,
+	allocator: RcCell<ShmAllocator>,
+	surface: RcCell<Surface>,
+	frames: Vec<CursorFrame>,
+	// of earlier cursors, still shown by the compositor until it releases them
+	stale: Vec<RcCell<Buffer>>,
+	current: usize,
+	frame_cb: Option<RcCell<Callback>>,
+	last_time: Option<u32>,
+	elapsed: u32,
+}
+
+impl CursorSurface {
+	pub fn new(
+		compositor: &Compositor,
+		allocator: RcCell<ShmAllocator>,
+		theme: CursorTheme,
+	) -> Result<RcCell<Self>, Box<dyn Error>> {
+		let surface = compositor.make_surface()?;
+		Ok(Rc::new(RefCell::new(Self {
+			theme,
+			name: None,
+			allocator,
+			surface,
+			frames: vec![],
+			stale: vec![],
+			current: 0,
+			frame_cb: None,
+			last_time: None,
+			elapsed: 0,
+		})))
+	}
+
+	pub fn surface_id(&self) -> Id {
+		self.surface.borrow().id
+	}
+
+	// of the frame on screen, animated cursors can move it between frames
+	pub fn hotspot(&self) -> (i32, i32) {
+		self.frames.get(self.current).map(|f| f.hotspot).unwrap_or_default()
+	}
+
+	pub fn set_cursor(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+		if self.name.as_deref() == Some(name) {
+			return Ok(());
+		}
+		let cursor = self.theme.load_cursor(name)?;
+		self.upload(&cursor)?;
+		self.name = Some(String::from(name));
+		self.show_frame(0)
+	}
+
+	// first name the theme has wins
+	pub fn set_cursor_from(&mut self, names: &[&str]) -> Result<(), Box<dyn Error>> {
+		let mut last_err: Box<dyn Error> = WaylandError::CursorNotFound.boxed();
+		for name in names {
+			match self.set_cursor(name) {
+				Ok(()) => return Ok(()),
+				Err(er) => last_err = er,
+			}
+		}
+		Err(last_err)
+	}
+
+	fn upload(&mut self, cursor: &Cursor) -> Result<(), Box<dyn Error>> {
+		// the id map keeps the buffers alive, so they have to be destroyed by hand,
+		// which also hands their ranges back to the allocator. busy ones wait for release
+		self.stale.extend(self.frames.drain(..).map(|frame| frame.buffer));
+		self.destroy_released()?;
+		self.frame_cb = None;
+
+		for img in &cursor.images {
+			let (w, h) = (img.width as i32, img.height as i32);
+			let buffer = Buffer::new_allocated(&self.allocator, (w, h), PixelFormat::Argb8888)?;
+			buffer.borrow_mut().map_mut()?.pixels()?.copy_from_slice(&img.pixels);
+			self.frames.push(CursorFrame {
+				buffer,
+				hotspot: (img.xhot as i32, img.yhot as i32),
+				delay: img.delay,
+			});
+		}
+		Ok(())
+	}
+
+	fn show_frame(&mut self, ix: usize) -> Result<(), Box<dyn Error>> {
+		let frame = self.frames.get(ix).ok_or(WaylandError::ObjectNonExistent)?;
+		let (w, h) = {
+			let buf = frame.buffer.borrow();
+			(buf.width, buf.height)
+		};
+		self.current = ix;
+		self.elapsed = 0;
+		let mut surface = self.surface.borrow_mut();
+		surface.attach_buffer_obj(frame.buffer.clone())?;
+		surface.damage_buffer(Region::new(0, 0, w, h))?;
+		if self.frames.len() > 1 {
+			self.frame_cb = Some(surface.frame()?);
+		}
+		surface.commit()
+	}
+
+	fn destroy_released(&mut self) -> Result<(), Box<dyn Error>> {
+		for ix in (0..self.stale.len()).rev() {
+			if !self.stale[ix].borrow().in_use {
+				self.stale.remove(ix).borrow_mut().destroy()?;
+			}
+		}
+		Ok(())
+	}
+
+	// animated cursors advance on frame callbacks, which only come while the cursor is visible
+	pub fn update(&mut self) -> Result<(), Box<dyn Error>> {
+		self.destroy_released()?;
+		if self.frames.len() < 2 {
+			return Ok(());
+		}
+		let time = match &self.frame_cb {
+			Some(cb) if cb.borrow().done => cb.borrow().data.unwrap_or_default(),
+			_ => return Ok(()),
+		};
+		if let Some(last) = self.last_time {
+			self.elapsed += time.wrapping_sub(last);
+		}
+		self.last_time = Some(time);
+
+		if self.elapsed >= self.frames[self.current].delay {
+			self.show_frame((self.current + 1) % self.frames.len())
+		} else {
+			let mut surface = self.surface.borrow_mut();
+			self.frame_cb = Some(surface.frame()?);
+			surface.commit()
+		}
+	}
+}
@@ -0,0 +1,217 @@
+use std::{cell::RefCell, error::Error, rc::Rc};
+
+use crate::wayland::{
+	CtxType, EventAction, RcCell, WaylandError, WaylandObject, WaylandObjectKind,
+	pointer::Pointer,
+	registry::Registry,
+	wire::{Id, WireArgument, WireRequest},
+};
+
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorShape {
+	Default = 1,
+	ContextMenu,
+	Help,
+	Pointer,
+	Progress,
+	Wait,
+	Cell,
+	Crosshair,
+	Text,
+	VerticalText,
+	Alias,
+	Copy,
+	Move,
+	NoDrop,
+	NotAllowed,
+	Grab,
+	Grabbing,
+	EResize,
+	NResize,
+	NeResize,
+	NwResize,
+	SResize,
+	SeResize,
+	SwResize,
+	WResize,
+	EwResize,
+	NsResize,
+	NeswResize,
+	NwseResize,
+	ColResize,
+	RowResize,
+	AllScroll,
+	ZoomIn,
+	ZoomOut,
+}
+
+impl CursorShape {
+	// xcursor names to try when the compositor can't set shapes for us,
+	// css name first and then the legacy x11 ones
+	pub fn xcursor_names(&self) -> &'static [&'static str] {
+		match self {
+			Self::Default => &["default", "left_ptr"],
+			Self::ContextMenu => &["context-menu", "left_ptr"],
+			Self::Help => &["help", "question_arrow"],
+			Self::Pointer => &["pointer", "hand2", "hand1"],
+			Self::Progress => &["progress", "left_ptr_watch"],
+			Self::Wait => &["wait", "watch"],
+			Self::Cell => &["cell", "plus"],
+			Self::Crosshair => &["crosshair", "cross"],
+			Self::Text => &["text", "xterm"],
+			Self::VerticalText => &["vertical-text", "xterm"],
+			Self::Alias => &["alias", "dnd-link"],
+			Self::Copy => &["copy", "dnd-copy"],
+			Self::Move => &["move", "dnd-move"],
+			Self::NoDrop => &["no-drop", "dnd-none"],
+			Self::NotAllowed => &["not-allowed", "crossed_circle"],
+			Self::Grab => &["grab", "openhand", "hand1"],
+			Self::Grabbing => &["grabbing", "closedhand", "fleur"],
+			Self::EResize => &["e-resize", "right_side"],
+			Self::NResize => &["n-resize", "top_side"],
+			Self::NeResize => &["ne-resize", "top_right_corner"],
+			Self::NwResize => &["nw-resize", "top_left_corner"],
+			Self::SResize => &["s-resize", "bottom_side"],
+			Self::SeResize => &["se-resize", "bottom_right_corner"],
+			Self::SwResize => &["sw-resize", "bottom_left_corner"],
+			Self::WResize => &["w-resize", "left_side"],
+			Self::EwResize => &["ew-resize", "sb_h_double_arrow"],
+			Self::NsResize => &["ns-resize", "sb_v_double_arrow"],
+			Self::NeswResize => &["nesw-resize", "fd_double_arrow"],
+			Self::NwseResize => &["nwse-resize", "bd_double_arrow"],
+			Self::ColResize => &["col-resize", "sb_h_double_arrow"],
+			Self::RowResize => &["row-resize", "sb_v_double_arrow"],
+			Self::AllScroll => &["all-scroll", "fleur"],
+			Self::ZoomIn => &["zoom-in"],
+			Self::ZoomOut => &["zoom-out"],
+		}
+	}
+}
+
+pub struct CursorShapeManager {
+	pub id: Id,
+	ctx: CtxType,
+}
+
+impl CursorShapeManager {
+	pub fn new_bound(registry: &mut Registry) -> Result<RcCell<Self>, Box<dyn Error>> {
+		let obj = Rc::new(RefCell::new(Self {
+			id: 0,
+			ctx: registry.ctx.clone(),
+		}));
+		let id = registry
+			.ctx
+			.borrow_mut()
+			.wlim
+			.new_id_registered(WaylandObjectKind::CursorShapeManager, obj.clone());
+		obj.borrow_mut().id = id;
+		registry.bind(id, WaylandObjectKind::CursorShapeManager, 1)?;
+		Ok(obj)
+	}
+
+	pub(crate) fn wl_destroy(&self) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 0,
+			args: vec![],
+		})
+	}
+
+	pub fn destroy(&self) -> Result<(), Box<dyn Error>> {
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_destroy()?)?;
+		self.ctx.borrow_mut().wlim.free_id(self.id)?;
+		Ok(())
+	}
+
+	pub(crate) fn wl_get_pointer(
+		&self,
+		id: Id,
+		pointer_id: Id,
+	) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 1,
+			args: vec![WireArgument::NewId(id), WireArgument::Obj(pointer_id)],
+		})
+	}
+
+	pub fn get_pointer(
+		&self,
+		pointer: &Pointer,
+	) -> Result<RcCell<CursorShapeDevice>, Box<dyn Error>> {
+		let device = Rc::new(RefCell::new(CursorShapeDevice {
+			id: 0,
+			ctx: self.ctx.clone(),
+		}));
+		let id = self
+			.ctx
+			.borrow_mut()
+			.wlim
+			.new_id_registered(WaylandObjectKind::CursorShapeDevice, device.clone());
+		device.borrow_mut().id = id;
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_get_pointer(id, pointer.id)?)?;
+		Ok(device)
+	}
+}
+
+pub struct CursorShapeDevice {
+	pub id: Id,
+	ctx: CtxType,
+}
+
+impl CursorShapeDevice {
+	pub(crate) fn wl_destroy(&self) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 0,
+			args: vec![],
+		})
+	}
+
+	pub fn destroy(&self) -> Result<(), Box<dyn Error>> {
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_destroy()?)?;
+		self.ctx.borrow_mut().wlim.free_id(self.id)?;
+		Ok(())
+	}
+
+	pub(crate) fn wl_set_shape(
+		&self,
+		serial: u32,
+		shape: CursorShape,
+	) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 1,
+			args: vec![WireArgument::UnInt(serial), WireArgument::UnInt(shape as u32)],
+		})
+	}
+}
+
+impl WaylandObject for CursorShapeManager {
+	fn handle(
+		&mut self,
+		opcode: super::OpCode,
+		_payload: &[u8],
+	) -> Result<Vec<EventAction>, Box<dyn Error>> {
+		Err(WaylandError::InvalidOpCode(opcode, self.as_str()).boxed())
+	}
+
+	fn as_str(&self) -> &'static str {
+		WaylandObjectKind::CursorShapeManager.as_str()
+	}
+}
+
+impl WaylandObject for CursorShapeDevice {
+	fn handle(
+		&mut self,
+		opcode: super::OpCode,
+		_payload: &[u8],
+	) -> Result<Vec<EventAction>, Box<dyn Error>> {
+		Err(WaylandError::InvalidOpCode(opcode, self.as_str()).boxed())
+	}
+
+	fn as_str(&self) -> &'static str {
+		WaylandObjectKind::CursorShapeDevice.as_str()
+	}
+}
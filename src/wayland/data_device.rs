@@ -0,0 +1,1046 @@
+use std::{
+	cell::RefCell,
+	error::Error,
+	ffi::OsString,
+	fs::File,
+	io::{ErrorKind, Read, Write},
+	os::{
+		fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+		unix::ffi::OsStringExt,
+	},
+	path::PathBuf,
+	rc::Rc,
+};
+
+use libc::{F_GETFL, F_SETFL, O_CLOEXEC, O_NONBLOCK, fcntl, pipe2};
+
+use crate::wayland::{
+	CtxType, DebugLevel, EventAction, RcCell, WaylandError, WaylandObject, WaylandObjectKind,
+	keyboard::Keyboard,
+	pointer::Pointer,
+	primary_selection::{PrimarySelectionDevice, PrimarySelectionDeviceManager},
+	registry::Registry,
+	seat::Seat,
+	surface::Surface,
+	wire::{FdQueue, Fixed, FromWirePayload, Id, WireArgument, WireRequest},
+};
+
+// best first, the x11 style ones are for xwayland clients
+pub const TEXT_MIME_TYPES: [&str; 5] =
+	["text/plain;charset=utf-8", "UTF8_STRING", "text/plain", "STRING", "TEXT"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionKind {
+	// ctrl+c and ctrl+v
+	Clipboard,
+	// whatever was last selected, pasted with a middle click
+	Primary,
+}
+
+pub const URI_LIST_MIME_TYPE: &str = "text/uri-list";
+
+// a bitmask of these goes over the wire, since wl_data_device_manager v3
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DndAction {
+	None = 0,
+	Copy = 1,
+	Move = 2,
+	// the target asks the user after the drop and sets the final action then
+	Ask = 4,
+}
+
+impl DndAction {
+	fn from_mask(mask: u32) -> Self {
+		match mask {
+			1 => Self::Copy,
+			2 => Self::Move,
+			4 => Self::Ask,
+			_ => Self::None,
+		}
+	}
+}
+
+pub struct DataDeviceManager {
+	pub id: Id,
+	ctx: CtxType,
+	pub(crate) version: u32,
+}
+
+impl DataDeviceManager {
+	pub fn new_bound(registry: &mut Registry) -> Result<RcCell<Self>, Box<dyn Error>> {
+		let version = registry
+			.does_implement(WaylandObjectKind::DataDeviceManager.as_str())
+			.ok_or(WaylandError::NotInRegistry)?
+			.min(3);
+		let obj = Rc::new(RefCell::new(Self {
+			id: 0,
+			ctx: registry.ctx.clone(),
+			version,
+		}));
+		let id = registry
+			.ctx
+			.borrow_mut()
+			.wlim
+			.new_id_registered(WaylandObjectKind::DataDeviceManager, obj.clone());
+		obj.borrow_mut().id = id;
+		registry.bind(id, WaylandObjectKind::DataDeviceManager, version)?;
+		Ok(obj)
+	}
+
+	pub(crate) fn wl_create_data_source(&self, id: Id) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 0,
+			args: vec![WireArgument::NewId(id)],
+		})
+	}
+
+	// data gets sent for whichever of the mime types is asked for
+	pub fn create_data_source(
+		&self,
+		mime_types: &[&str],
+		data: Vec<u8>,
+	) -> Result<RcCell<DataSource>, Box<dyn Error>> {
+		let fds = self.ctx.borrow().wlmm.fds.clone();
+		let source = Rc::new(RefCell::new(DataSource {
+			id: 0,
+			ctx: self.ctx.clone(),
+			version: self.version,
+			mime_types: vec![],
+			contents: SourceData::new(fds, data),
+			cancelled: false,
+			target: None,
+			action: DndAction::None,
+			drop_performed: false,
+			finished: false,
+		}));
+		let id = self
+			.ctx
+			.borrow_mut()
+			.wlim
+			.new_id_registered(WaylandObjectKind::DataSource, source.clone());
+		source.borrow_mut().id = id;
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_create_data_source(id)?)?;
+		for mime in mime_types {
+			source.borrow_mut().offer(mime)?;
+		}
+		Ok(source)
+	}
+
+	pub(crate) fn wl_get_data_device(
+		&self,
+		id: Id,
+		seat_id: Id,
+	) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 1,
+			args: vec![WireArgument::NewId(id), WireArgument::Obj(seat_id)],
+		})
+	}
+}
+
+pub struct DataDevice {
+	pub id: Id,
+	ctx: CtxType,
+	manager: RcCell<DataDeviceManager>,
+	// announced by data_offer, waiting for the selection they belong to
+	offers: Vec<RcCell<DataOffer>>,
+	// what the clipboard holds, None when it's empty
+	pub selection: Option<RcCell<DataOffer>>,
+	// our own clipboard content while we own it
+	source: Option<RcCell<DataSource>>,
+	// the drag over one of our surfaces, and where it is in surface coordinates
+	pub drag: Option<RcCell<DataOffer>>,
+	pub drag_focus: Option<Id>,
+	pub drag_position: (f64, f64),
+	// dropped on us, waiting for receive_drop
+	pub dropped: Option<RcCell<DataOffer>>,
+	// a drag we started, dropping it on ourselves must not go through the compositor
+	drag_source: Option<RcCell<DataSource>>,
+	// set when the drop ended that drag
+	dropped_source: Option<RcCell<DataSource>>,
+	retired: Retired,
+	primary_manager: Option<RcCell<PrimarySelectionDeviceManager>>,
+	pub primary: Option<RcCell<PrimarySelectionDevice>>,
+}
+
+impl DataDevice {
+	pub fn new_from_manager(
+		manager: &RcCell<DataDeviceManager>,
+		seat: &Seat,
+	) -> Result<RcCell<Self>, Box<dyn Error>> {
+		let ctx = manager.borrow().ctx.clone();
+		let device = Rc::new(RefCell::new(Self {
+			id: 0,
+			ctx: ctx.clone(),
+			manager: manager.clone(),
+			offers: vec![],
+			selection: None,
+			source: None,
+			drag: None,
+			drag_focus: None,
+			drag_position: (0.0, 0.0),
+			dropped: None,
+			drag_source: None,
+			dropped_source: None,
+			retired: Retired::default(),
+			primary_manager: None,
+			primary: None,
+		}));
+		let id =
+			ctx.borrow_mut().wlim.new_id_registered(WaylandObjectKind::DataDevice, device.clone());
+		device.borrow_mut().id = id;
+		ctx.borrow().wlmm.send_request(&mut manager.borrow().wl_get_data_device(id, seat.id)?)?;
+		Ok(device)
+	}
+
+	pub(crate) fn wl_start_drag(
+		&self,
+		source_id: Id,
+		origin_id: Id,
+		icon_id: Id,
+		serial: u32,
+	) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 0,
+			args: vec![
+				WireArgument::Obj(source_id),
+				WireArgument::Obj(origin_id),
+				WireArgument::Obj(icon_id),
+				WireArgument::UnInt(serial),
+			],
+		})
+	}
+
+	// from the latest button press on origin, which has to be still held.
+	// the icon surface is positioned relative to the pointer by its buffer offset
+	pub fn start_drag(
+		&mut self,
+		pointer: &Pointer,
+		source: RcCell<DataSource>,
+		origin: &Surface,
+		icon: Option<&Surface>,
+	) -> Result<(), Box<dyn Error>> {
+		let serial = pointer.button_serial.ok_or(WaylandError::NoSerial)?;
+		let icon_id = icon.map_or(0, |icon| icon.id);
+		let mut req = self.wl_start_drag(source.borrow().id, origin.id, icon_id, serial)?;
+		self.ctx.borrow().wlmm.send_request(&mut req)?;
+		let old = self.drag_source.replace(source);
+		self.retired.retire(old.map(|old| old as _));
+		Ok(())
+	}
+
+	// receives the dropped data and lets the source know it's done.
+	// an ask drop needs set_actions with the final action before this.
+	// blocks until the source has written all of it, same as get_clipboard_text
+	// None if nothing was dropped or not as mime
+	pub fn receive_drop(&mut self, mime: &str) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+		let Some(offer) = self.dropped.take() else {
+			return Ok(None);
+		};
+		let offer = offer.borrow();
+		let source = self.dropped_source.take();
+		let data = if !offer.has_mime_type(mime) {
+			None
+		} else if let Some(source) = &source {
+			Some(source.borrow().contents.data.clone())
+		} else {
+			let mut data = vec![];
+			offer.receive(mime)?.read_to_end(&mut data)?;
+			Some(data)
+		};
+		self.retired.retire(source.map(|source| source as _));
+		offer.finish()?;
+		offer.destroy()?;
+		Ok(data)
+	}
+
+	// takes file drops for the drag over us, as a copy. call on enter, false if it has no files
+	pub fn accept_uris(&self) -> Result<bool, Box<dyn Error>> {
+		let Some(offer) = &self.drag else {
+			return Ok(false);
+		};
+		let offer = offer.borrow();
+		if !offer.has_mime_type(URI_LIST_MIME_TYPE) {
+			offer.accept(None)?;
+			return Ok(false);
+		}
+		offer.accept(Some(URI_LIST_MIME_TYPE))?;
+		offer.set_actions(DndAction::Copy as u32, DndAction::Copy)?;
+		Ok(true)
+	}
+
+	// file drops, as uris
+	pub fn receive_dropped_uris(&mut self) -> Result<Vec<String>, Box<dyn Error>> {
+		let data = self.receive_drop(URI_LIST_MIME_TYPE)?.unwrap_or_default();
+		Ok(parse_uri_list(&String::from_utf8_lossy(&data)))
+	}
+
+	pub(crate) fn wl_set_selection(
+		&self,
+		source_id: Id,
+		serial: u32,
+	) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 1,
+			args: vec![WireArgument::Obj(source_id), WireArgument::UnInt(serial)],
+		})
+	}
+
+	// serial of the input event that caused it, None clears the clipboard
+	pub fn set_selection(
+		&mut self,
+		source: Option<RcCell<DataSource>>,
+		serial: u32,
+	) -> Result<(), Box<dyn Error>> {
+		let id = source.as_ref().map_or(0, |s| s.borrow().id);
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_set_selection(id, serial)?)?;
+		let old = std::mem::replace(&mut self.source, source);
+		self.retired.retire(old.map(|old| old as _));
+		Ok(())
+	}
+
+	// sends to other clients only write what their pipe has room for, call this in the event loop
+	pub fn flush_transfers(&mut self) -> Result<(), Box<dyn Error>> {
+		let retired = self.retired.flush();
+		for source in self.source.iter().chain(&self.drag_source) {
+			source.borrow_mut().contents.flush()?;
+		}
+		if let Some(primary) = &self.primary {
+			primary.borrow_mut().flush_transfers()?;
+		}
+		retired
+	}
+
+	// false if the compositor has no primary selection, which then stays empty
+	pub fn enable_primary_selection(
+		&mut self,
+		registry: &mut Registry,
+		seat: &Seat,
+	) -> Result<bool, Box<dyn Error>> {
+		if self.primary.is_some() {
+			return Ok(true);
+		}
+		let kind = WaylandObjectKind::PrimarySelectionDeviceManager;
+		if registry.does_implement(kind.as_str()).is_none() {
+			return Ok(false);
+		}
+		let manager = PrimarySelectionDeviceManager::new_bound(registry)?;
+		self.primary = Some(PrimarySelectionDevice::new_from_manager(&manager, seat)?);
+		self.primary_manager = Some(manager);
+		Ok(true)
+	}
+
+	// the primary selection needs enable_primary_selection first
+	pub fn set_clipboard_text(
+		&mut self,
+		kind: SelectionKind,
+		keyboard: &Keyboard,
+		text: &str,
+	) -> Result<(), Box<dyn Error>> {
+		let serial = keyboard.serial.ok_or(WaylandError::NoSerial)?;
+		match kind {
+			SelectionKind::Clipboard => {
+				let source = self
+					.manager
+					.borrow()
+					.create_data_source(&TEXT_MIME_TYPES, text.as_bytes().to_vec())?;
+				self.set_selection(Some(source), serial)
+			}
+			SelectionKind::Primary => match &self.primary {
+				Some(primary) => primary.borrow_mut().set_text(serial, text),
+				None => Err(WaylandError::NoPrimarySelection.boxed()),
+			},
+		}
+	}
+
+	// blocks until the owner has written all of it and closed the pipe, so a stuck owner
+	// stalls the caller. None if there's no text in the selection
+	pub fn get_clipboard_text(
+		&self,
+		kind: SelectionKind,
+	) -> Result<Option<String>, Box<dyn Error>> {
+		if kind == SelectionKind::Primary {
+			return match &self.primary {
+				Some(primary) => primary.borrow().get_text(),
+				None => Ok(None),
+			};
+		}
+		// asking ourselves through the compositor would wait on events we can't handle here
+		if let Some(source) = &self.source
+			&& !source.borrow().cancelled
+		{
+			return Ok(Some(source.borrow().contents.text()));
+		}
+		self.selection.as_ref().map_or(Ok(None), |offer| offer.borrow().read_text())
+	}
+
+	pub(crate) fn wl_release(&self) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 2,
+			args: vec![],
+		})
+	}
+
+	pub fn release(&self) -> Result<(), Box<dyn Error>> {
+		// release only exists since v2
+		if self.manager.borrow().version >= 2 {
+			self.ctx.borrow().wlmm.send_request(&mut self.wl_release()?)?;
+		}
+		self.ctx.borrow_mut().wlim.free_id(self.id)?;
+		if let (Some(primary), Some(manager)) = (&self.primary, &self.primary_manager) {
+			primary.borrow().destroy()?;
+			manager.borrow().destroy()?;
+		}
+		Ok(())
+	}
+}
+
+pub struct DataOffer {
+	pub id: Id,
+	ctx: CtxType,
+	version: u32,
+	pub mime_types: Vec<String>,
+	// what the source allows and what the compositor picked from that and set_actions
+	pub source_actions: u32,
+	pub action: DndAction,
+	// of the enter that brought the drag
+	serial: u32,
+}
+
+impl DataOffer {
+	pub fn has_mime_type(&self, mime: &str) -> bool {
+		self.mime_types.iter().any(|m| m == mime)
+	}
+
+	pub(crate) fn wl_accept(&self, mime: Option<&str>) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 0,
+			args: vec![
+				WireArgument::UnInt(self.serial),
+				WireArgument::String(mime.unwrap_or_default().to_string()),
+			],
+		})
+	}
+
+	// during a drag, which mime type a drop would be taken as. None refuses it
+	pub fn accept(&self, mime: Option<&str>) -> Result<(), Box<dyn Error>> {
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_accept(mime)?)
+	}
+
+	pub(crate) fn wl_receive(&self, mime: &str, fd: i32) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 1,
+			args: vec![WireArgument::String(mime.to_string()), WireArgument::FileDescriptor(fd)],
+		})
+	}
+
+	// the other end is handed to the owner, which writes the data and closes it
+	pub fn receive(&self, mime: &str) -> Result<File, Box<dyn Error>> {
+		let (read, write) = make_pipe()?;
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_receive(mime, write.as_raw_fd())?)?;
+		Ok(read)
+	}
+
+	pub(crate) fn wl_destroy(&self) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 2,
+			args: vec![],
+		})
+	}
+
+	pub fn destroy(&self) -> Result<(), Box<dyn Error>> {
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_destroy()?)?;
+		self.ctx.borrow_mut().wlim.free_id(self.id)?;
+		Ok(())
+	}
+
+	pub(crate) fn wl_finish(&self) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 3,
+			args: vec![],
+		})
+	}
+
+	// after a drop has been received, needs wl_data_device_manager v3
+	pub fn finish(&self) -> Result<(), Box<dyn Error>> {
+		if self.version >= 3 {
+			self.ctx.borrow().wlmm.send_request(&mut self.wl_finish()?)?;
+		}
+		Ok(())
+	}
+
+	pub(crate) fn wl_set_actions(
+		&self,
+		actions: u32,
+		preferred: DndAction,
+	) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 4,
+			args: vec![WireArgument::UnInt(actions), WireArgument::UnInt(preferred as u32)],
+		})
+	}
+
+	// the actions the target takes, as a DndAction mask, preferred has to be one of them
+	pub fn set_actions(&self, actions: u32, preferred: DndAction) -> Result<(), Box<dyn Error>> {
+		if self.version >= 3 {
+			self.ctx.borrow().wlmm.send_request(&mut self.wl_set_actions(actions, preferred)?)?;
+		}
+		Ok(())
+	}
+}
+
+impl SelectionOffer for DataOffer {
+	fn offer_id(&self) -> Id {
+		self.id
+	}
+
+	fn offered(&self) -> &[String] {
+		&self.mime_types
+	}
+
+	fn open(&self, mime: &str) -> Result<File, Box<dyn Error>> {
+		self.receive(mime)
+	}
+
+	fn destroy_request(&self) -> Result<WireRequest, Box<dyn Error>> {
+		self.wl_destroy()
+	}
+}
+
+pub struct DataSource {
+	pub id: Id,
+	ctx: CtxType,
+	version: u32,
+	pub mime_types: Vec<String>,
+	contents: SourceData,
+	// replaced by another selection or the drag went nowhere, it's destroyed by then
+	pub cancelled: bool,
+	// the mime type the drag target accepted, None if it won't take a drop
+	pub target: Option<String>,
+	// what the compositor settled on for the drag
+	pub action: DndAction,
+	pub drop_performed: bool,
+	// the target got everything, a move can delete the data now. it's destroyed by then
+	pub finished: bool,
+}
+
+impl DataSource {
+	pub(crate) fn wl_offer(&self, mime: &str) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 0,
+			args: vec![WireArgument::String(mime.to_string())],
+		})
+	}
+
+	pub fn offer(&mut self, mime: &str) -> Result<(), Box<dyn Error>> {
+		self.mime_types.push(mime.to_string());
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_offer(mime)?)
+	}
+
+	pub(crate) fn wl_destroy(&self) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 1,
+			args: vec![],
+		})
+	}
+
+	pub fn destroy(&self) -> Result<(), Box<dyn Error>> {
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_destroy()?)?;
+		self.ctx.borrow_mut().wlim.free_id(self.id)?;
+		Ok(())
+	}
+
+	pub(crate) fn wl_set_actions(&self, actions: u32) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 2,
+			args: vec![WireArgument::UnInt(actions)],
+		})
+	}
+
+	// DndAction mask of what a drag may do with the data, before start_drag.
+	// needs wl_data_device_manager v3, older ones always copy
+	pub fn set_actions(&self, actions: u32) -> Result<(), Box<dyn Error>> {
+		if self.version >= 3 {
+			self.ctx.borrow().wlmm.send_request(&mut self.wl_set_actions(actions)?)?;
+		}
+		Ok(())
+	}
+}
+
+impl SelectionSource for DataSource {
+	fn contents(&mut self) -> &mut SourceData {
+		&mut self.contents
+	}
+}
+
+// what a source hands out, same for the clipboard and the primary selection.
+// writes into readers' pipes without blocking, the rest goes out on later flushes.
+// a pipe only holds 64 KiB, and the reader may only start once we're back in the event loop
+pub(crate) struct SourceData {
+	fds: FdQueue,
+	pub(crate) data: Vec<u8>,
+	// pipe and how much of the data went into it
+	pending: Vec<(File, usize)>,
+}
+
+impl SourceData {
+	pub(crate) fn new(fds: FdQueue, data: Vec<u8>) -> Self {
+		Self {
+			fds,
+			data,
+			pending: vec![],
+		}
+	}
+
+	pub(crate) fn text(&self) -> String {
+		String::from_utf8_lossy(&self.data).into_owned()
+	}
+
+	// the send event of the source named by who
+	pub(crate) fn send(
+		&mut self,
+		payload: &[u8],
+		who: String,
+		pending: &mut Vec<EventAction>,
+	) -> Result<(), Box<dyn Error>> {
+		let mime = String::from_wire(payload)?;
+		let fd = self.fds.borrow_mut().pop_front().ok_or(WaylandError::MissingFd)?;
+		// a reader that went away shouldn't take the event loop with it
+		if let Err(er) = self.start(fd) {
+			pending.push(EventAction::Error(er));
+		}
+		pending.push(EventAction::DebugMessage(
+			DebugLevel::Verbose,
+			format!("{} sent as {}", who, mime),
+		));
+		Ok(())
+	}
+
+	fn start(&mut self, fd: RawFd) -> Result<(), Box<dyn Error>> {
+		let pipe = unsafe { File::from_raw_fd(fd) };
+		let flags = unsafe { fcntl(fd, F_GETFL) };
+		if flags < 0 || unsafe { fcntl(fd, F_SETFL, flags | O_NONBLOCK) } != 0 {
+			return Err(Box::new(std::io::Error::last_os_error()));
+		}
+		self.pending.push((pipe, 0));
+		self.flush().map(|_| ())
+	}
+
+	// true once everything is sent. finished pipes get closed, which is the reader's end of data.
+	// broken ones are dropped too, their error comes back after the rest had their turn
+	pub(crate) fn flush(&mut self) -> Result<bool, Box<dyn Error>> {
+		let mut err = None;
+		let data = &self.data;
+		self.pending.retain_mut(|(pipe, sent)| {
+			while *sent < data.len() {
+				match pipe.write(&data[*sent..]) {
+					Ok(n) => *sent += n,
+					Err(er) if er.kind() == ErrorKind::Interrupted => {}
+					Err(er) if er.kind() == ErrorKind::WouldBlock => return true,
+					Err(er) => {
+						err = Some(er);
+						return false;
+					}
+				}
+			}
+			false
+		});
+		match err {
+			Some(er) => Err(Box::new(er)),
+			None => Ok(self.pending.is_empty()),
+		}
+	}
+}
+
+// lets Retired hold either kind of source
+pub(crate) trait SelectionSource {
+	fn contents(&mut self) -> &mut SourceData;
+}
+
+// replaced sources still writing to readers, kept so their pipes stay open
+#[derive(Default)]
+pub(crate) struct Retired(Vec<Rc<RefCell<dyn SelectionSource>>>);
+
+impl Retired {
+	pub(crate) fn retire(&mut self, source: Option<Rc<RefCell<dyn SelectionSource>>>) {
+		if let Some(source) = source
+			&& !source.borrow_mut().contents().pending.is_empty()
+		{
+			self.0.push(source);
+		}
+	}
+
+	pub(crate) fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+		let mut result = Ok(());
+		self.0.retain(|source| {
+			let mut source = source.borrow_mut();
+			let done = match source.contents().flush() {
+				Ok(done) => done,
+				Err(er) => {
+					result = Err(er);
+					source.contents().pending.is_empty()
+				}
+			};
+			!done
+		});
+		result
+	}
+}
+
+// what the clipboard and primary selection offers have in common
+pub(crate) trait SelectionOffer {
+	fn offer_id(&self) -> Id;
+	fn offered(&self) -> &[String];
+	fn open(&self, mime: &str) -> Result<File, Box<dyn Error>>;
+	fn destroy_request(&self) -> Result<WireRequest, Box<dyn Error>>;
+
+	// for handlers, which can't reach the context
+	fn discard(&self, pending: &mut Vec<EventAction>) -> Result<(), Box<dyn Error>> {
+		pending.push(EventAction::Request(self.destroy_request()?));
+		pending.push(EventAction::IdDeletion(self.offer_id()));
+		Ok(())
+	}
+
+	// blocks until the owner has written all of it and closed the pipe, so a stuck owner
+	// stalls the caller. None if it has no text
+	fn read_text(&self) -> Result<Option<String>, Box<dyn Error>> {
+		let Some(mime) = TEXT_MIME_TYPES.iter().find(|m| self.offered().iter().any(|o| o == *m))
+		else {
+			return Ok(None);
+		};
+		let mut data = vec![];
+		self.open(mime)?.read_to_end(&mut data)?;
+		Ok(Some(String::from_utf8_lossy(&data).into_owned()))
+	}
+}
+
+// announced by data_offer, the selection or drag event that follows takes it out
+pub(crate) fn take_offer<T: SelectionOffer>(
+	offers: &mut Vec<RcCell<T>>,
+	id: Id,
+) -> Option<RcCell<T>> {
+	let ix = offers.iter().position(|offer| offer.borrow().offer_id() == id)?;
+	Some(offers.remove(ix))
+}
+
+// read and write ends, the write end goes to whoever owns the data
+pub(crate) fn make_pipe() -> Result<(File, OwnedFd), Box<dyn Error>> {
+	let mut fds = [0; 2];
+	if unsafe { pipe2(fds.as_mut_ptr(), O_CLOEXEC) } != 0 {
+		return Err(Box::new(std::io::Error::last_os_error()));
+	}
+	Ok(unsafe { (File::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) })
+}
+
+// one uri per line, # starts a comment
+pub fn parse_uri_list(list: &str) -> Vec<String> {
+	list.lines()
+		.map(|line| line.trim())
+		.filter(|line| !line.is_empty() && !line.starts_with('#'))
+		.map(String::from)
+		.collect()
+}
+
+// local file uris to paths, percent escapes decoded
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+	let rest = uri.strip_prefix("file://")?;
+	let (host, path) = rest.split_at(rest.find('/')?);
+	// anything else names another machine
+	if !host.is_empty() && host != "localhost" {
+		return None;
+	}
+	let mut bytes = vec![];
+	let mut iter = path.bytes();
+	while let Some(b) = iter.next() {
+		if b == b'%' {
+			let hex = [iter.next()?, iter.next()?];
+			bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+		} else {
+			bytes.push(b);
+		}
+	}
+	Some(PathBuf::from(OsString::from_vec(bytes)))
+}
+
+impl WaylandObject for DataDeviceManager {
+	fn handle(
+		&mut self,
+		opcode: super::OpCode,
+		_payload: &[u8],
+	) -> Result<Vec<EventAction>, Box<dyn Error>> {
+		Err(WaylandError::InvalidOpCode(opcode, self.as_str()).boxed())
+	}
+
+	fn as_str(&self) -> &'static str {
+		WaylandObjectKind::DataDeviceManager.as_str()
+	}
+}
+
+impl WaylandObject for DataDevice {
+	fn handle(
+		&mut self,
+		opcode: super::OpCode,
+		payload: &[u8],
+	) -> Result<Vec<EventAction>, Box<dyn Error>> {
+		let mut pending = vec![];
+		let p = payload;
+		match opcode {
+			// data_offer
+			0 => {
+				let id = u32::from_wire(p)?;
+				let offer = Rc::new(RefCell::new(DataOffer {
+					id,
+					ctx: self.ctx.clone(),
+					version: self.manager.borrow().version,
+					mime_types: vec![],
+					source_actions: 0,
+					action: DndAction::None,
+					serial: 0,
+				}));
+				self.offers.push(offer.clone());
+				pending.push(EventAction::NewObject(id, WaylandObjectKind::DataOffer, offer));
+			}
+			// enter
+			1 => {
+				let serial = u32::from_wire(p)?;
+				let surface = u32::from_wire(&p[4..])?;
+				let x = Fixed::from_wire(&p[8..])?;
+				let y = Fixed::from_wire(&p[12..])?;
+				let id = u32::from_wire(&p[16..])?;
+				if let Some(old) = self.drag.take() {
+					old.borrow().discard(&mut pending)?;
+				}
+				// no offer when the drag comes from a client without a source
+				self.drag = take_offer(&mut self.offers, id);
+				if let Some(offer) = &self.drag {
+					offer.borrow_mut().serial = serial;
+				}
+				self.drag_focus = Some(surface);
+				self.drag_position = (x.to_f64(), y.to_f64());
+				pending.push(EventAction::DebugMessage(
+					DebugLevel::Verbose,
+					format!("{} drag entered surface {}", self.as_str(), surface),
+				));
+			}
+			// leave
+			2 => {
+				if let Some(offer) = self.drag.take() {
+					offer.borrow().discard(&mut pending)?;
+				}
+				self.drag_focus = None;
+			}
+			// motion
+			3 => {
+				let x = Fixed::from_wire(&p[4..])?;
+				let y = Fixed::from_wire(&p[8..])?;
+				self.drag_position = (x.to_f64(), y.to_f64());
+			}
+			// drop, a leave follows which must not take the offer along
+			4 => {
+				if let Some(old) = self.dropped.take() {
+					old.borrow().discard(&mut pending)?;
+				}
+				self.dropped = self.drag.take();
+				// a seat has one drag at a time, so while ours is still going this drop ends it
+				let source = self.drag_source.take();
+				self.dropped_source = source.clone().filter(|source| {
+					let source = source.borrow();
+					!source.cancelled && !source.finished
+				});
+				self.retired.retire(source.map(|source| source as _));
+			}
+			// selection
+			5 => {
+				let id = u32::from_wire(p)?;
+				if let Some(old) = self.selection.take() {
+					old.borrow().discard(&mut pending)?;
+				}
+				self.selection = take_offer(&mut self.offers, id);
+				pending.push(EventAction::DebugMessage(
+					DebugLevel::Verbose,
+					format!("{} selection is now offer {}", self.as_str(), id),
+				));
+			}
+			inv => return Err(WaylandError::InvalidOpCode(inv, self.as_str()).boxed()),
+		}
+		Ok(pending)
+	}
+
+	fn as_str(&self) -> &'static str {
+		WaylandObjectKind::DataDevice.as_str()
+	}
+}
+
+impl WaylandObject for DataOffer {
+	fn handle(
+		&mut self,
+		opcode: super::OpCode,
+		payload: &[u8],
+	) -> Result<Vec<EventAction>, Box<dyn Error>> {
+		match opcode {
+			// offer
+			0 => {
+				self.mime_types.push(String::from_wire(payload)?);
+			}
+			// source_actions
+			1 => {
+				self.source_actions = u32::from_wire(payload)?;
+			}
+			// action
+			2 => {
+				self.action = DndAction::from_mask(u32::from_wire(payload)?);
+			}
+			inv => return Err(WaylandError::InvalidOpCode(inv, self.as_str()).boxed()),
+		}
+		Ok(vec![])
+	}
+
+	fn as_str(&self) -> &'static str {
+		WaylandObjectKind::DataOffer.as_str()
+	}
+}
+
+impl WaylandObject for DataSource {
+	fn handle(
+		&mut self,
+		opcode: super::OpCode,
+		payload: &[u8],
+	) -> Result<Vec<EventAction>, Box<dyn Error>> {
+		let mut pending = vec![];
+		match opcode {
+			// target
+			0 => {
+				let mime = String::from_wire(payload)?;
+				self.target = (!mime.is_empty()).then_some(mime);
+			}
+			// send
+			1 => {
+				let who = format!("{} {}", self.as_str(), self.id);
+				self.contents.send(payload, who, &mut pending)?;
+			}
+			// cancelled
+			2 => {
+				self.cancelled = true;
+				pending.push(EventAction::Request(self.wl_destroy()?));
+				pending.push(EventAction::IdDeletion(self.id));
+			}
+			// dnd_drop_performed
+			3 => {
+				self.drop_performed = true;
+			}
+			// dnd_finished
+			4 => {
+				self.finished = true;
+				pending.push(EventAction::Request(self.wl_destroy()?));
+				pending.push(EventAction::IdDeletion(self.id));
+			}
+			// action
+			5 => {
+				self.action = DndAction::from_mask(u32::from_wire(payload)?);
+			}
+			inv => return Err(WaylandError::InvalidOpCode(inv, self.as_str()).boxed()),
+		}
+		Ok(pending)
+	}
+
+	fn as_str(&self) -> &'static str {
+		WaylandObjectKind::DataSource.as_str()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{io::Read, os::unix::net::UnixStream};
+
+	use super::*;
+	use crate::wayland::{Context, IdentManager, wire::MessageManager};
+
+	fn source(ctx: &CtxType, data: &[u8]) -> RcCell<DataSource> {
+		let fds = ctx.borrow().wlmm.fds.clone();
+		let source = Rc::new(RefCell::new(DataSource {
+			id: 0,
+			ctx: ctx.clone(),
+			version: 3,
+			mime_types: vec![String::from("text/plain")],
+			contents: SourceData::new(fds, data.to_vec()),
+			cancelled: false,
+			target: None,
+			action: DndAction::None,
+			drop_performed: false,
+			finished: false,
+		}));
+		let id =
+			ctx.borrow_mut().wlim.new_id_registered(WaylandObjectKind::DataSource, source.clone());
+		source.borrow_mut().id = id;
+		source
+	}
+
+	// the compositor's end, sends a send event with a fresh pipe
+	fn send(compositor: &MessageManager, id: Id) -> File {
+		let (read, write) = make_pipe().unwrap();
+		let mut event = WireRequest {
+			sender_id: id,
+			opcode: 1,
+			args: vec![
+				WireArgument::String(String::from("text/plain")),
+				WireArgument::FileDescriptor(write.as_raw_fd()),
+			],
+		};
+		compositor.send_request(&mut event).unwrap();
+		read
+	}
+
+	#[test]
+	fn uri_list() {
+		let list = "# comment\r\nfile:///tmp/a%20b\r\n\r\n  https://example.org/  \r\n";
+		assert_eq!(parse_uri_list(list), ["file:///tmp/a%20b", "https://example.org/"]);
+	}
+
+	#[test]
+	fn uris_to_paths() {
+		assert_eq!(uri_to_path("file:///tmp/a%20b"), Some(PathBuf::from("/tmp/a b")));
+		assert_eq!(uri_to_path("file://localhost/tmp/%C3%A9"), Some(PathBuf::from("/tmp/é")));
+		assert_eq!(uri_to_path("file://otherhost/tmp/x"), None);
+		assert_eq!(uri_to_path("https://example.org/x"), None);
+		assert_eq!(uri_to_path("file:///tmp/%2"), None);
+		assert_eq!(uri_to_path("file://"), None);
+	}
+
+	#[test]
+	fn send_to_zombie_source() {
+		let (ours, theirs) = UnixStream::pair().unwrap();
+		let wlmm = MessageManager::from_socket(ours).unwrap();
+		let ctx = Rc::new(RefCell::new(Context::new(wlmm, IdentManager::default())));
+		let compositor = MessageManager::from_socket(theirs).unwrap();
+
+		let zombie = source(&ctx, b"old");
+		let live = source(&ctx, b"new");
+		zombie.borrow().destroy().unwrap();
+		let mut zombie_pipe = send(&compositor, zombie.borrow().id);
+		let mut live_pipe = send(&compositor, live.borrow().id);
+		// each message comes with its own fds, so they may take a read each
+		ctx.borrow_mut().handle_events().unwrap();
+		ctx.borrow_mut().handle_events().unwrap();
+
+		// closed unwritten, not handed to the live source
+		let mut data = vec![];
+		zombie_pipe.read_to_end(&mut data).unwrap();
+		assert!(data.is_empty());
+		live_pipe.read_to_end(&mut data).unwrap();
+		assert_eq!(data, b"new");
+	}
+}
@@ -1,5 +1,5 @@
 use crate::wayland::{
-	CtxType, EventAction, OpCode, RcCell, RecvError, WaylandError, WaylandObject,
+	CtxType, DebugLevel, EventAction, OpCode, RcCell, RecvError, WaylandError, WaylandObject,
 	WaylandObjectKind,
 	callback::Callback,
 	registry::Registry,
@@ -72,7 +72,13 @@ impl WaylandObject for Display {
 				let code = u32::from_wire(&p[4..])?;
 				let message = String::from_wire(&p[8..])?;
 				// maybe add some sort of error manager
-				eprintln!("======== ERROR {} FIRED in wl_display\nfor object\n{:?}", code, message);
+				pending.push(EventAction::DebugMessage(
+					DebugLevel::Severe,
+					format!(
+						"======== ERROR {} FIRED in wl_display\nfor object\n{:?}",
+						code, message
+					),
+				));
 				pending.push(EventAction::Error(
 					RecvError {
 						id: obj_id,
@@ -89,7 +95,7 @@ impl WaylandObject for Display {
 				// 	deleted_id
 				// );
 				// self.ctx.borrow_mut().wlim.free_id(deleted_id)?;
-				pending.push(EventAction::IdDeletion(deleted_id));
+				pending.push(EventAction::IdDeleted(deleted_id));
 			}
 			inv => {
 				return Err(WaylandError::InvalidOpCode(inv, self.as_str()).boxed());
@@ -0,0 +1,443 @@
+use std::{collections::HashMap, error::Error, fs, path::Path};
+
+use crate::wayland::{
+	WaylandError,
+	canvas::{Canvas, Color},
+	region::Region,
+};
+
+const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+const PSF1_MODE512: u8 = 0x01;
+const PSF1_MODEHASTAB: u8 = 0x02;
+const PSF2_MAGIC: [u8; 4] = [0x72, 0xb5, 0x4a, 0x86];
+const PSF2_HAS_UNICODE_TABLE: u32 = 0x01;
+
+// rows of 1 bpp, msb is the leftmost pixel and every row starts on a new byte
+#[derive(Debug, Clone)]
+pub struct Glyph {
+	pub width: u32,
+	pub height: u32,
+	// from the top left of the cell
+	pub x: i32,
+	pub y: i32,
+	pub advance: u32,
+	rows: Vec<u8>,
+}
+
+impl Glyph {
+	fn new(width: u32, height: u32, rows: Vec<u8>) -> Self {
+		Self {
+			width,
+			height,
+			x: 0,
+			y: 0,
+			advance: width,
+			rows,
+		}
+	}
+
+	pub fn is_set(&self, x: u32, y: u32) -> bool {
+		let row_len = self.width.div_ceil(8);
+		self.rows
+			.get((y * row_len + x / 8) as usize)
+			.is_some_and(|byte| byte & (0x80 >> (x % 8)) != 0)
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct Font {
+	// distance between baselines
+	pub line_height: u32,
+	glyphs: HashMap<char, Glyph>,
+	// drawn for chars the font doesn't have
+	fallback: Option<Glyph>,
+}
+
+fn read_u32(data: &[u8], at: usize) -> Result<u32, WaylandError> {
+	data.get(at..at + 4)
+		.map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+		.ok_or(WaylandError::InvalidFont)
+}
+
+impl Font {
+	pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+		Ok(Self::parse(&fs::read(path)?)?)
+	}
+
+	// psf1, psf2 or bdf, told apart by their magic
+	pub fn parse(data: &[u8]) -> Result<Self, WaylandError> {
+		if data.starts_with(&PSF2_MAGIC) {
+			Self::parse_psf2(data)
+		} else if data.starts_with(&PSF1_MAGIC) {
+			Self::parse_psf1(data)
+		} else if data.starts_with(b"STARTFONT") {
+			Self::parse_bdf(std::str::from_utf8(data).map_err(|_| WaylandError::InvalidFont)?)
+		} else {
+			Err(WaylandError::InvalidFont)
+		}
+	}
+
+	// glyphs without a unicode table are taken to be in codepoint order
+	fn from_cells(cells: Vec<Glyph>, table: Option<Vec<Vec<char>>>, line_height: u32) -> Self {
+		let mut glyphs = HashMap::new();
+		for (ix, glyph) in cells.iter().enumerate() {
+			match &table {
+				Some(table) => {
+					for ch in table.get(ix).into_iter().flatten() {
+						glyphs.entry(*ch).or_insert(glyph.clone());
+					}
+				}
+				None => {
+					if let Some(ch) = char::from_u32(ix as u32) {
+						glyphs.insert(ch, glyph.clone());
+					}
+				}
+			}
+		}
+		let fallback = glyphs.get(&'?').or(cells.first()).cloned();
+		Self {
+			line_height,
+			glyphs,
+			fallback,
+		}
+	}
+
+	fn parse_psf1(data: &[u8]) -> Result<Self, WaylandError> {
+		let mode = *data.get(2).ok_or(WaylandError::InvalidFont)?;
+		let height = *data.get(3).ok_or(WaylandError::InvalidFont)? as usize;
+		let count = if mode & PSF1_MODE512 != 0 {
+			512
+		} else {
+			256
+		};
+		let glyphs_end = 4 + count * height;
+		let cells = data
+			.get(4..glyphs_end)
+			.ok_or(WaylandError::InvalidFont)?
+			.chunks_exact(height.max(1))
+			.map(|rows| Glyph::new(8, height as u32, rows.to_vec()))
+			.collect();
+
+		// u16 codepoints per glyph ending in 0xffff, sequences after 0xfffe are skipped
+		let table = (mode & PSF1_MODEHASTAB != 0).then(|| {
+			let mut table = vec![vec![]; count];
+			let mut entries =
+				data[glyphs_end..].chunks_exact(2).map(|b| u16::from_le_bytes([b[0], b[1]]));
+			for chars in table.iter_mut() {
+				let mut in_sequence = false;
+				for entry in entries.by_ref() {
+					match entry {
+						0xffff => break,
+						0xfffe => in_sequence = true,
+						cp if !in_sequence => chars.extend(char::from_u32(cp as u32)),
+						_ => (),
+					}
+				}
+			}
+			table
+		});
+		Ok(Self::from_cells(cells, table, height as u32))
+	}
+
+	fn parse_psf2(data: &[u8]) -> Result<Self, WaylandError> {
+		let header_len = read_u32(data, 8)? as usize;
+		let flags = read_u32(data, 12)?;
+		let count = read_u32(data, 16)? as usize;
+		let glyph_len = read_u32(data, 20)? as usize;
+		let height = read_u32(data, 24)?;
+		let width = read_u32(data, 28)?;
+		let needed = width.div_ceil(8).checked_mul(height).ok_or(WaylandError::InvalidFont)?;
+		if glyph_len < needed as usize || glyph_len == 0 {
+			return Err(WaylandError::InvalidFont);
+		}
+		let glyphs_end = header_len + count * glyph_len;
+		let cells = data
+			.get(header_len..glyphs_end)
+			.ok_or(WaylandError::InvalidFont)?
+			.chunks_exact(glyph_len)
+			.map(|rows| Glyph::new(width, height, rows.to_vec()))
+			.collect();
+
+		// utf-8 strings per glyph ending in 0xff, sequences after 0xfe are skipped
+		let table = (flags & PSF2_HAS_UNICODE_TABLE != 0).then(|| {
+			data[glyphs_end..]
+				.split(|b| *b == 0xff)
+				.take(count)
+				.map(|entry| {
+					let single = entry.split(|b| *b == 0xfe).next().unwrap_or_default();
+					String::from_utf8_lossy(single).chars().collect()
+				})
+				.collect()
+		});
+		Ok(Self::from_cells(cells, table, height))
+	}
+
+	fn parse_bdf(text: &str) -> Result<Self, WaylandError> {
+		let nums = |rest: &str| -> Result<Vec<i32>, WaylandError> {
+			rest.split_whitespace()
+				.map(|n| n.parse::<i32>().map_err(|_| WaylandError::InvalidFont))
+				.collect()
+		};
+
+		let mut bbox = None;
+		let (mut ascent, mut descent, mut default_char) = (None, None, None);
+		// encoding, advance, (w, h, x, y), rows
+		let mut chars = vec![];
+		let mut current: Option<(i32, i32, [i32; 4], Vec<u8>)> = None;
+		let mut in_bitmap = false;
+
+		for line in text.lines().map(str::trim) {
+			let (key, rest) = line.split_once(' ').unwrap_or((line, ""));
+			if in_bitmap {
+				if key == "ENDCHAR" {
+					in_bitmap = false;
+					chars.extend(current.take());
+				} else if let Some((_, _, [w, ..], rows)) = &mut current {
+					let row_len = (*w as usize).div_ceil(8);
+					for ix in 0..row_len {
+						let byte = line.get(ix * 2..ix * 2 + 2).unwrap_or("00");
+						rows.push(
+							u8::from_str_radix(byte, 16).map_err(|_| WaylandError::InvalidFont)?,
+						);
+					}
+				}
+				continue;
+			}
+			match key {
+				"FONTBOUNDINGBOX" => bbox = Some(nums(rest)?),
+				"FONT_ASCENT" => ascent = nums(rest)?.first().copied(),
+				"FONT_DESCENT" => descent = nums(rest)?.first().copied(),
+				"DEFAULT_CHAR" => default_char = nums(rest)?.first().copied(),
+				"STARTCHAR" => current = Some((-1, 0, [0; 4], vec![])),
+				"ENCODING" => {
+					if let (Some(current), Some(enc)) = (&mut current, nums(rest)?.first()) {
+						current.0 = *enc;
+					}
+				}
+				"DWIDTH" => {
+					if let (Some(current), Some(dx)) = (&mut current, nums(rest)?.first()) {
+						current.1 = *dx;
+					}
+				}
+				"BBX" => {
+					if let (Some(current), [w, h, x, y]) = (&mut current, nums(rest)?.as_slice()) {
+						// the width sizes the bitmap rows. zero is fine, spaces often have no bitmap
+						if *w < 0 || *h < 0 {
+							return Err(WaylandError::InvalidFont);
+						}
+						current.2 = [*w, *h, *x, *y];
+					}
+				}
+				"BITMAP" => in_bitmap = true,
+				_ => (),
+			}
+		}
+
+		let bbox = bbox.ok_or(WaylandError::InvalidFont)?;
+		let [_, bbox_h, _, bbox_y] = bbox.as_slice() else {
+			return Err(WaylandError::InvalidFont);
+		};
+		let ascent = ascent.unwrap_or(bbox_h + bbox_y);
+		let descent = descent.unwrap_or(-bbox_y);
+
+		let mut glyphs = HashMap::new();
+		let mut fallback = None;
+		for (encoding, advance, [w, h, x, y], rows) in chars {
+			let glyph = Glyph {
+				width: w.max(0) as u32,
+				height: h.max(0) as u32,
+				x,
+				// bdf offsets go up from the baseline
+				y: ascent - (y + h),
+				advance: if advance > 0 {
+					advance
+				} else {
+					w
+				}
+				.max(0) as u32,
+				// any BITMAP lines of an empty glyph are meaningless
+				rows: if w == 0 || h == 0 {
+					vec![]
+				} else {
+					rows
+				},
+			};
+			if Some(encoding) == default_char {
+				fallback = Some(glyph.clone());
+			}
+			if let Some(ch) = u32::try_from(encoding).ok().and_then(char::from_u32) {
+				glyphs.insert(ch, glyph);
+			}
+		}
+		let fallback = fallback.or(glyphs.get(&'?').cloned());
+		Ok(Self {
+			line_height: (ascent + descent).max(0) as u32,
+			glyphs,
+			fallback,
+		})
+	}
+
+	// 8x8 ascii, for when there is no font on disk
+	pub fn builtin() -> Self {
+		let cells = (0..0x20)
+			.map(|_| [0; 8])
+			.chain(BUILTIN_8X8)
+			.map(|rows| Glyph::new(8, 8, rows.iter().map(|row| row.reverse_bits()).collect()))
+			.collect();
+		Self::from_cells(cells, None, 8)
+	}
+
+	pub fn glyph(&self, ch: char) -> Option<&Glyph> {
+		self.glyphs.get(&ch).or(self.fallback.as_ref())
+	}
+
+	// scale is a whole factor, the buffer scale on hidpi outputs
+	pub fn measure(&self, text: &str, scale: u32) -> (u32, u32) {
+		let width = text
+			.lines()
+			.map(|line| {
+				line.chars().filter_map(|ch| self.glyph(ch)).map(|g| g.advance).sum::<u32>()
+			})
+			.max()
+			.unwrap_or(0);
+		let lines = text.lines().count() as u32;
+		(width * scale, lines * self.line_height * scale)
+	}
+
+	// pos is the top left of the first line, returns where the text ended
+	pub fn draw(
+		&self,
+		canvas: &mut Canvas,
+		(x, y): (i32, i32),
+		text: &str,
+		color: Color,
+		scale: u32,
+	) -> (i32, i32) {
+		let scale = scale.max(1) as i32;
+		let (mut pen_x, mut pen_y) = (x, y);
+		for ch in text.chars() {
+			if ch == '\n' {
+				pen_x = x;
+				pen_y += self.line_height as i32 * scale;
+				continue;
+			}
+			let Some(glyph) = self.glyph(ch) else {
+				continue;
+			};
+			for gy in 0..glyph.height {
+				for gx in (0..glyph.width).filter(|gx| glyph.is_set(*gx, gy)) {
+					canvas.fill_rect(
+						Region::new(
+							pen_x + (glyph.x + gx as i32) * scale,
+							pen_y + (glyph.y + gy as i32) * scale,
+							scale,
+							scale,
+						),
+						color,
+					);
+				}
+			}
+			pen_x += glyph.advance as i32 * scale;
+		}
+		(pen_x, pen_y)
+	}
+}
+
+// font8x8_basic by daniel hepper (public domain), 0x20..=0x7e, lsb is the leftmost pixel
+const BUILTIN_8X8: [[u8; 8]; 95] = [
+	[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+	[0x18, 0x3c, 0x3c, 0x18, 0x18, 0x00, 0x18, 0x00], // !
+	[0x36, 0x36, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // "
+	[0x36, 0x36, 0x7f, 0x36, 0x7f, 0x36, 0x36, 0x00], // #
+	[0x0c, 0x3e, 0x03, 0x1e, 0x30, 0x1f, 0x0c, 0x00], // $
+	[0x00, 0x63, 0x33, 0x18, 0x0c, 0x66, 0x63, 0x00], // %
+	[0x1c, 0x36, 0x1c, 0x6e, 0x3b, 0x33, 0x6e, 0x00], // &
+	[0x06, 0x06, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00], // '
+	[0x18, 0x0c, 0x06, 0x06, 0x06, 0x0c, 0x18, 0x00], // (
+	[0x06, 0x0c, 0x18, 0x18, 0x18, 0x0c, 0x06, 0x00], // )
+	[0x00, 0x66, 0x3c, 0xff, 0x3c, 0x66, 0x00, 0x00], // *
+	[0x00, 0x0c, 0x0c, 0x3f, 0x0c, 0x0c, 0x00, 0x00], // +
+	[0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c, 0x06], // ,
+	[0x00, 0x00, 0x00, 0x3f, 0x00, 0x00, 0x00, 0x00], // -
+	[0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c, 0x00], // .
+	[0x60, 0x30, 0x18, 0x0c, 0x06, 0x03, 0x01, 0x00], // /
+	[0x3e, 0x63, 0x73, 0x7b, 0x6f, 0x67, 0x3e, 0x00], // 0
+	[0x0c, 0x0e, 0x0c, 0x0c, 0x0c, 0x0c, 0x3f, 0x00], // 1
+	[0x1e, 0x33, 0x30, 0x1c, 0x06, 0x33, 0x3f, 0x00], // 2
+	[0x1e, 0x33, 0x30, 0x1c, 0x30, 0x33, 0x1e, 0x00], // 3
+	[0x38, 0x3c, 0x36, 0x33, 0x7f, 0x30, 0x78, 0x00], // 4
+	[0x3f, 0x03, 0x1f, 0x30, 0x30, 0x33, 0x1e, 0x00], // 5
+	[0x1c, 0x06, 0x03, 0x1f, 0x33, 0x33, 0x1e, 0x00], // 6
+	[0x3f, 0x33, 0x30, 0x18, 0x0c, 0x0c, 0x0c, 0x00], // 7
+	[0x1e, 0x33, 0x33, 0x1e, 0x33, 0x33, 0x1e, 0x00], // 8
+	[0x1e, 0x33, 0x33, 0x3e, 0x30, 0x18, 0x0e, 0x00], // 9
+	[0x00, 0x0c, 0x0c, 0x00, 0x00, 0x0c, 0x0c, 0x00], // :
+	[0x00, 0x0c, 0x0c, 0x00, 0x00, 0x0c, 0x0c, 0x06], // ;
+	[0x18, 0x0c, 0x06, 0x03, 0x06, 0x0c, 0x18, 0x00], // <
+	[0x00, 0x00, 0x3f, 0x00, 0x00, 0x3f, 0x00, 0x00], // =
+	[0x06, 0x0c, 0x18, 0x30, 0x18, 0x0c, 0x06, 0x00], // >
+	[0x1e, 0x33, 0x30, 0x18, 0x0c, 0x00, 0x0c, 0x00], // ?
+	[0x3e, 0x63, 0x7b, 0x7b, 0x7b, 0x03, 0x1e, 0x00], // @
+	[0x0c, 0x1e, 0x33, 0x33, 0x3f, 0x33, 0x33, 0x00], // A
+	[0x3f, 0x66, 0x66, 0x3e, 0x66, 0x66, 0x3f, 0x00], // B
+	[0x3c, 0x66, 0x03, 0x03, 0x03, 0x66, 0x3c, 0x00], // C
+	[0x1f, 0x36, 0x66, 0x66, 0x66, 0x36, 0x1f, 0x00], // D
+	[0x7f, 0x46, 0x16, 0x1e, 0x16, 0x46, 0x7f, 0x00], // E
+	[0x7f, 0x46, 0x16, 0x1e, 0x16, 0x06, 0x0f, 0x00], // F
+	[0x3c, 0x66, 0x03, 0x03, 0x73, 0x66, 0x7c, 0x00], // G
+	[0x33, 0x33, 0x33, 0x3f, 0x33, 0x33, 0x33, 0x00], // H
+	[0x1e, 0x0c, 0x0c, 0x0c, 0x0c, 0x0c, 0x1e, 0x00], // I
+	[0x78, 0x30, 0x30, 0x30, 0x33, 0x33, 0x1e, 0x00], // J
+	[0x67, 0x66, 0x36, 0x1e, 0x36, 0x66, 0x67, 0x00], // K
+	[0x0f, 0x06, 0x06, 0x06, 0x46, 0x66, 0x7f, 0x00], // L
+	[0x63, 0x77, 0x7f, 0x7f, 0x6b, 0x63, 0x63, 0x00], // M
+	[0x63, 0x67, 0x6f, 0x7b, 0x73, 0x63, 0x63, 0x00], // N
+	[0x1c, 0x36, 0x63, 0x63, 0x63, 0x36, 0x1c, 0x00], // O
+	[0x3f, 0x66, 0x66, 0x3e, 0x06, 0x06, 0x0f, 0x00], // P
+	[0x1e, 0x33, 0x33, 0x33, 0x3b, 0x1e, 0x38, 0x00], // Q
+	[0x3f, 0x66, 0x66, 0x3e, 0x36, 0x66, 0x67, 0x00], // R
+	[0x1e, 0x33, 0x07, 0x0e, 0x38, 0x33, 0x1e, 0x00], // S
+	[0x3f, 0x2d, 0x0c, 0x0c, 0x0c, 0x0c, 0x1e, 0x00], // T
+	[0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x3f, 0x00], // U
+	[0x33, 0x33, 0x33, 0x33, 0x33, 0x1e, 0x0c, 0x00], // V
+	[0x63, 0x63, 0x63, 0x6b, 0x7f, 0x77, 0x63, 0x00], // W
+	[0x63, 0x63, 0x36, 0x1c, 0x1c, 0x36, 0x63, 0x00], // X
+	[0x33, 0x33, 0x33, 0x1e, 0x0c, 0x0c, 0x1e, 0x00], // Y
+	[0x7f, 0x63, 0x31, 0x18, 0x4c, 0x66, 0x7f, 0x00], // Z
+	[0x1e, 0x06, 0x06, 0x06, 0x06, 0x06, 0x1e, 0x00], // [
+	[0x03, 0x06, 0x0c, 0x18, 0x30, 0x60, 0x40, 0x00], // \
+	[0x1e, 0x18, 0x18, 0x18, 0x18, 0x18, 0x1e, 0x00], // ]
+	[0x08, 0x1c, 0x36, 0x63, 0x00, 0x00, 0x00, 0x00], // ^
+	[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff], // _
+	[0x0c, 0x0c, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00], // `
+	[0x00, 0x00, 0x1e, 0x30, 0x3e, 0x33, 0x6e, 0x00], // a
+	[0x07, 0x06, 0x06, 0x3e, 0x66, 0x66, 0x3b, 0x00], // b
+	[0x00, 0x00, 0x1e, 0x33, 0x03, 0x33, 0x1e, 0x00], // c
+	[0x38, 0x30, 0x30, 0x3e, 0x33, 0x33, 0x6e, 0x00], // d
+	[0x00, 0x00, 0x1e, 0x33, 0x3f, 0x03, 0x1e, 0x00], // e
+	[0x1c, 0x36, 0x06, 0x0f, 0x06, 0x06, 0x0f, 0x00], // f
+	[0x00, 0x00, 0x6e, 0x33, 0x33, 0x3e, 0x30, 0x1f], // g
+	[0x07, 0x06, 0x36, 0x6e, 0x66, 0x66, 0x67, 0x00], // h
+	[0x0c, 0x00, 0x0e, 0x0c, 0x0c, 0x0c, 0x1e, 0x00], // i
+	[0x30, 0x00, 0x30, 0x30, 0x30, 0x33, 0x33, 0x1e], // j
+	[0x07, 0x06, 0x66, 0x36, 0x1e, 0x36, 0x67, 0x00], // k
+	[0x0e, 0x0c, 0x0c, 0x0c, 0x0c, 0x0c, 0x1e, 0x00], // l
+	[0x00, 0x00, 0x33, 0x7f, 0x7f, 0x6b, 0x63, 0x00], // m
+	[0x00, 0x00, 0x1f, 0x33, 0x33, 0x33, 0x33, 0x00], // n
+	[0x00, 0x00, 0x1e, 0x33, 0x33, 0x33, 0x1e, 0x00], // o
+	[0x00, 0x00, 0x3b, 0x66, 0x66, 0x3e, 0x06, 0x0f], // p
+	[0x00, 0x00, 0x6e, 0x33, 0x33, 0x3e, 0x30, 0x78], // q
+	[0x00, 0x00, 0x3b, 0x6e, 0x66, 0x06, 0x0f, 0x00], // r
+	[0x00, 0x00, 0x3e, 0x03, 0x1e, 0x30, 0x1f, 0x00], // s
+	[0x08, 0x0c, 0x3e, 0x0c, 0x0c, 0x2c, 0x18, 0x00], // t
+	[0x00, 0x00, 0x33, 0x33, 0x33, 0x33, 0x6e, 0x00], // u
+	[0x00, 0x00, 0x33, 0x33, 0x33, 0x1e, 0x0c, 0x00], // v
+	[0x00, 0x00, 0x63, 0x6b, 0x7f, 0x7f, 0x36, 0x00], // w
+	[0x00, 0x00, 0x63, 0x36, 0x1c, 0x36, 0x63, 0x00], // x
+	[0x00, 0x00, 0x33, 0x33, 0x33, 0x3e, 0x30, 0x1f], // y
+	[0x00, 0x00, 0x3f, 0x19, 0x0c, 0x26, 0x3f, 0x00], // z
+	[0x38, 0x0c, 0x0c, 0x07, 0x0c, 0x0c, 0x38, 0x00], // {
+	[0x18, 0x18, 0x18, 0x00, 0x18, 0x18, 0x18, 0x00], // |
+	[0x07, 0x0c, 0x0c, 0x38, 0x0c, 0x0c, 0x07, 0x00], // }
+	[0x6e, 0x3b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ~
+];
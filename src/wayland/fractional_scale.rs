@@ -0,0 +1,142 @@
+use std::{cell::RefCell, error::Error, rc::Rc};
+
+use crate::wayland::{
+	CtxType, Event, EventAction, RcCell, WaylandError, WaylandObject, WaylandObjectKind,
+	registry::Registry,
+	surface::Surface,
+	wire::{FromWirePayload, Id, WireArgument, WireRequest},
+};
+
+pub struct FractionalScaleManager {
+	pub id: Id,
+	ctx: CtxType,
+}
+
+impl FractionalScaleManager {
+	pub fn new_bound(registry: &mut Registry) -> Result<RcCell<Self>, Box<dyn Error>> {
+		let obj = Rc::new(RefCell::new(Self {
+			id: 0,
+			ctx: registry.ctx.clone(),
+		}));
+		let id = registry
+			.ctx
+			.borrow_mut()
+			.wlim
+			.new_id_registered(WaylandObjectKind::FractionalScaleManager, obj.clone());
+		obj.borrow_mut().id = id;
+		registry.bind(id, WaylandObjectKind::FractionalScaleManager, 1)?;
+		Ok(obj)
+	}
+
+	pub(crate) fn wl_destroy(&self) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 0,
+			args: vec![],
+		})
+	}
+
+	pub fn destroy(&self) -> Result<(), Box<dyn Error>> {
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_destroy()?)?;
+		self.ctx.borrow_mut().wlim.free_id(self.id)?;
+		Ok(())
+	}
+
+	pub(crate) fn wl_get_fractional_scale(
+		&self,
+		id: Id,
+		surface_id: Id,
+	) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 1,
+			args: vec![WireArgument::NewId(id), WireArgument::Obj(surface_id)],
+		})
+	}
+
+	// the surface needs a viewport too for the scale to be used, see Viewporter::get_viewport
+	pub fn get_fractional_scale(
+		&self,
+		surface: &Surface,
+	) -> Result<RcCell<FractionalScale>, Box<dyn Error>> {
+		let obj = Rc::new(RefCell::new(FractionalScale {
+			id: 0,
+			ctx: self.ctx.clone(),
+			surface_id: surface.id,
+			scale: None,
+		}));
+		let id = self
+			.ctx
+			.borrow_mut()
+			.wlim
+			.new_id_registered(WaylandObjectKind::FractionalScale, obj.clone());
+		obj.borrow_mut().id = id;
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_get_fractional_scale(id, surface.id)?)?;
+		Ok(obj)
+	}
+}
+
+pub struct FractionalScale {
+	pub id: Id,
+	ctx: CtxType,
+	surface_id: Id,
+	// in 120ths, 180 is 1.5x
+	pub scale: Option<u32>,
+}
+
+impl FractionalScale {
+	pub(crate) fn wl_destroy(&self) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 0,
+			args: vec![],
+		})
+	}
+
+	pub fn destroy(&self) -> Result<(), Box<dyn Error>> {
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_destroy()?)?;
+		self.ctx.borrow_mut().wlim.free_id(self.id)?;
+		Ok(())
+	}
+}
+
+impl WaylandObject for FractionalScaleManager {
+	fn handle(
+		&mut self,
+		opcode: super::OpCode,
+		_payload: &[u8],
+	) -> Result<Vec<EventAction>, Box<dyn Error>> {
+		Err(WaylandError::InvalidOpCode(opcode, self.as_str()).boxed())
+	}
+
+	fn as_str(&self) -> &'static str {
+		WaylandObjectKind::FractionalScaleManager.as_str()
+	}
+}
+
+impl WaylandObject for FractionalScale {
+	fn handle(
+		&mut self,
+		opcode: super::OpCode,
+		payload: &[u8],
+	) -> Result<Vec<EventAction>, Box<dyn Error>> {
+		let mut pending = vec![];
+		match opcode {
+			// preferred_scale
+			0 => {
+				let scale = u32::from_wire(payload)?;
+				self.scale = Some(scale);
+				pending.push(EventAction::Event(Event::PreferredFractionalScale(
+					self.surface_id,
+					scale,
+				)));
+			}
+			inv => return Err(WaylandError::InvalidOpCode(inv, self.as_str()).boxed()),
+		}
+		Ok(pending)
+	}
+
+	fn as_str(&self) -> &'static str {
+		WaylandObjectKind::FractionalScale.as_str()
+	}
+}
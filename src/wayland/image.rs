@@ -0,0 +1,592 @@
+use std::{error::Error, fs, path::Path};
+
+use crate::wayland::{
+	WaylandError,
+	allocator::stride_for,
+	canvas::{self, Color, Pixmap},
+	inflate::zlib_decompress,
+	shm::PixelFormat,
+};
+
+const PNG_MAGIC: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+// keeps a bogus header from asking for gigabytes
+const MAX_PIXELS: u64 = 1 << 28;
+// x, y, dx, dy of every adam7 pass
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+	(0, 0, 8, 8),
+	(4, 0, 8, 8),
+	(0, 4, 4, 8),
+	(2, 0, 4, 4),
+	(0, 2, 2, 4),
+	(1, 0, 2, 2),
+	(0, 1, 1, 2),
+];
+
+fn new_pixmap(width: u64, height: u64) -> Result<Pixmap, WaylandError> {
+	if width == 0 || height == 0 || width.checked_mul(height).is_none_or(|n| n > MAX_PIXELS) {
+		return Err(WaylandError::InvalidImage);
+	}
+	Ok(Pixmap::new(width as u32, height as u32))
+}
+
+fn rgba(r: u8, g: u8, b: u8, a: u8) -> u32 {
+	Color::rgba(r, g, b, a).premultiplied()
+}
+
+pub fn load(path: impl AsRef<Path>) -> Result<Pixmap, Box<dyn Error>> {
+	Ok(decode(&fs::read(path)?)?)
+}
+
+// format is told apart by the magic
+pub fn decode(data: &[u8]) -> Result<Pixmap, WaylandError> {
+	match data {
+		[0x89, b'P', b'N', b'G', ..] => decode_png(data),
+		[b'q', b'o', b'i', b'f', ..] => decode_qoi(data),
+		[b'B', b'M', ..] => decode_bmp(data),
+		[b'P', b'2' | b'3' | b'5' | b'6' | b'7', ..] => decode_ppm(data),
+		_ => Err(WaylandError::InvalidImage),
+	}
+}
+
+// premultiplied argb to whatever the buffer holds, rows padded like stride_for
+pub fn encode(image: &Pixmap, format: PixelFormat) -> Result<Vec<u8>, WaylandError> {
+	if !canvas::drawable(format) {
+		return Err(WaylandError::UndrawableFormat(format));
+	}
+	let bpp = format.bytes_per_pixel();
+	let stride = stride_for(image.width as i32, format.bits_per_pixel() as i32) as usize;
+	let mut out = vec![0; stride * image.height as usize];
+	for (row, pixels) in
+		out.chunks_exact_mut(stride).zip(image.pixels.chunks_exact(image.width as usize))
+	{
+		for (px, argb) in row.chunks_exact_mut(bpp).zip(pixels) {
+			canvas::encode(format, *argb, px);
+		}
+	}
+	Ok(out)
+}
+
+// netpbm: P2/P5 graymaps, P3/P6 pixmaps and P7 pam
+pub fn decode_ppm(data: &[u8]) -> Result<Pixmap, WaylandError> {
+	if data.len() < 2 || data[0] != b'P' {
+		return Err(WaylandError::InvalidImage);
+	}
+	let mut at = 2;
+	let (width, height, depth, maxval) = if data[1] == b'7' {
+		let (mut width, mut height, mut depth, mut maxval) = (0, 0, 0, 0);
+		loop {
+			let end = at
+				+ data[at..].iter().position(|b| *b == b'\n').ok_or(WaylandError::InvalidImage)?;
+			let line =
+				std::str::from_utf8(&data[at..end]).map_err(|_| WaylandError::InvalidImage)?;
+			at = end + 1;
+			let (key, value) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+			let value = || value.trim().parse::<u64>().map_err(|_| WaylandError::InvalidImage);
+			match key {
+				"WIDTH" => width = value()?,
+				"HEIGHT" => height = value()?,
+				"DEPTH" => depth = value()?,
+				"MAXVAL" => maxval = value()?,
+				"ENDHDR" => break,
+				_ => (),
+			}
+		}
+		(width, height, depth, maxval)
+	} else {
+		let mut header = [0u64; 3];
+		for value in header.iter_mut() {
+			// whitespace and comments up to the next number
+			loop {
+				match data.get(at) {
+					Some(b'#') => {
+						at += data[at..].iter().position(|b| *b == b'\n').unwrap_or(data.len() - at)
+					}
+					Some(b) if b.is_ascii_whitespace() => at += 1,
+					Some(_) => break,
+					None => return Err(WaylandError::InvalidImage),
+				}
+			}
+			let len = data[at..].iter().take_while(|b| b.is_ascii_digit()).count();
+			*value = std::str::from_utf8(&data[at..at + len])
+				.ok()
+				.and_then(|n| n.parse().ok())
+				.ok_or(WaylandError::InvalidImage)?;
+			at += len;
+		}
+		// a single whitespace byte before binary data
+		at += 1;
+		let depth = if matches!(data[1], b'2' | b'5') {
+			1
+		} else {
+			3
+		};
+		(header[0], header[1], depth, header[2])
+	};
+	if !(1..=65535).contains(&maxval) || !(1..=4).contains(&depth) {
+		return Err(WaylandError::InvalidImage);
+	}
+
+	let mut image = new_pixmap(width, height)?;
+	let count = image.pixels.len() * depth as usize;
+	let samples: Vec<u64> = if matches!(data[1], b'2' | b'3') {
+		data.get(at..)
+			.unwrap_or_default()
+			.split(|b| b.is_ascii_whitespace())
+			.filter(|word| !word.is_empty())
+			.take(count)
+			.map(|word| std::str::from_utf8(word).ok().and_then(|n| n.parse().ok()).unwrap_or(0))
+			.collect()
+	} else {
+		let size = if maxval > 255 {
+			2
+		} else {
+			1
+		};
+		data.get(at..)
+			.unwrap_or_default()
+			.chunks_exact(size)
+			.take(count)
+			.map(|s| {
+				if size == 2 {
+					u16::from_be_bytes([s[0], s[1]]) as u64
+				} else {
+					s[0] as u64
+				}
+			})
+			.collect()
+	};
+	if samples.len() < count {
+		return Err(WaylandError::InvalidImage);
+	}
+
+	let scale = |s: u64| (s.min(maxval) * 255 / maxval) as u8;
+	for (px, s) in image.pixels.iter_mut().zip(samples.chunks_exact(depth as usize)) {
+		*px = match s {
+			[v] => rgba(scale(*v), scale(*v), scale(*v), 255),
+			[v, a] => rgba(scale(*v), scale(*v), scale(*v), scale(*a)),
+			[r, g, b] => rgba(scale(*r), scale(*g), scale(*b), 255),
+			[r, g, b, a] => rgba(scale(*r), scale(*g), scale(*b), scale(*a)),
+			_ => 0,
+		};
+	}
+	Ok(image)
+}
+
+pub fn decode_qoi(data: &[u8]) -> Result<Pixmap, WaylandError> {
+	if !data.starts_with(b"qoif") {
+		return Err(WaylandError::InvalidImage);
+	}
+	let be32 = |at: usize| {
+		data.get(at..at + 4)
+			.map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+			.ok_or(WaylandError::InvalidImage)
+	};
+	let mut image = new_pixmap(be32(4)? as u64, be32(8)? as u64)?;
+
+	let mut seen = [[0u8; 4]; 64];
+	let mut px = [0u8, 0, 0, 255];
+	let mut at = 14;
+	let mut run = 0;
+	let mut byte = || {
+		let b = data.get(at).copied().ok_or(WaylandError::InvalidImage);
+		at += 1;
+		b
+	};
+	for out in image.pixels.iter_mut() {
+		if run > 0 {
+			run -= 1;
+		} else {
+			match byte()? {
+				0xfe => {
+					px = [byte()?, byte()?, byte()?, px[3]];
+				}
+				0xff => {
+					px = [byte()?, byte()?, byte()?, byte()?];
+				}
+				op => match op >> 6 {
+					0 => px = seen[(op & 0x3f) as usize],
+					1 => {
+						px[0] = px[0].wrapping_add((op >> 4) & 3).wrapping_sub(2);
+						px[1] = px[1].wrapping_add((op >> 2) & 3).wrapping_sub(2);
+						px[2] = px[2].wrapping_add(op & 3).wrapping_sub(2);
+					}
+					2 => {
+						let dg = (op & 0x3f).wrapping_sub(32);
+						let next = byte()?;
+						px[0] = px[0].wrapping_add(dg).wrapping_add(next >> 4).wrapping_sub(8);
+						px[1] = px[1].wrapping_add(dg);
+						px[2] = px[2].wrapping_add(dg).wrapping_add(next & 0x0f).wrapping_sub(8);
+					}
+					_ => run = op & 0x3f,
+				},
+			}
+			let [r, g, b, a] = px.map(|c| c as usize);
+			seen[(r * 3 + g * 5 + b * 7 + a * 11) % 64] = px;
+		}
+		*out = rgba(px[0], px[1], px[2], px[3]);
+	}
+	Ok(image)
+}
+
+// widens the bits under mask to 8
+fn masked(value: u32, mask: u32) -> Option<u8> {
+	if mask == 0 {
+		return None;
+	}
+	let bits = mask.count_ones();
+	let v = (value & mask) >> mask.trailing_zeros();
+	Some(if bits >= 8 {
+		(v >> (bits - 8)) as u8
+	} else {
+		(v * 255 / ((1 << bits) - 1)) as u8
+	})
+}
+
+// uncompressed and bitfields bmps of 1, 4, 8, 16, 24 and 32 bits
+pub fn decode_bmp(data: &[u8]) -> Result<Pixmap, WaylandError> {
+	if !data.starts_with(b"BM") {
+		return Err(WaylandError::InvalidImage);
+	}
+	let le32 = |at: usize| {
+		data.get(at..at + 4)
+			.map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+			.ok_or(WaylandError::InvalidImage)
+	};
+	let le16 = |at: usize| le32(at).map(|v| v & 0xffff);
+	let pixels_at = le32(10)? as usize;
+	let header_len = le32(14)? as usize;
+	let width = le32(18)? as i32;
+	let height = le32(22)? as i32;
+	let bpp = le16(28)?;
+	let compression = le32(30)?;
+	let colors_used = le32(46)? as usize;
+
+	// BI_RGB, BI_BITFIELDS, BI_ALPHABITFIELDS
+	let masks = match (compression, bpp) {
+		(0, 16) => [0x7c00, 0x03e0, 0x001f, 0],
+		(0, 32) => [0xff0000, 0x00ff00, 0x0000ff, 0],
+		(3 | 6, 16 | 32) => {
+			let alpha = if header_len >= 56 || compression == 6 {
+				le32(66)?
+			} else {
+				0
+			};
+			[le32(54)?, le32(58)?, le32(62)?, alpha]
+		}
+		(0, 1 | 4 | 8 | 24) => [0; 4],
+		_ => return Err(WaylandError::InvalidImage),
+	};
+	let palette_at = 14
+		+ header_len
+		+ if header_len == 40 && compression == 3 {
+			12
+		} else {
+			0
+		};
+	let palette: Vec<u32> = if bpp <= 8 {
+		let count = if colors_used == 0 {
+			1 << bpp
+		} else {
+			colors_used.min(256)
+		};
+		(0..count)
+			.map(|ix| le32(palette_at + ix * 4).map(|bgr| bgr | 0xff000000))
+			.collect::<Result<_, _>>()?
+	} else {
+		vec![]
+	};
+
+	let mut image = new_pixmap(width.unsigned_abs() as u64, height.unsigned_abs() as u64)?;
+	let (w, h) = (image.width as usize, image.height as usize);
+	let stride = (w * bpp as usize).div_ceil(32) * 4;
+	let raster = data.get(pixels_at..pixels_at + stride * h).ok_or(WaylandError::InvalidImage)?;
+	let mut any_alpha = false;
+	for y in 0..h {
+		// positive heights are stored bottom up
+		let src = &raster[if height > 0 {
+			h - 1 - y
+		} else {
+			y
+		} * stride..][..stride];
+		for x in 0..w {
+			let px = match bpp {
+				1 | 4 | 8 => {
+					let bit = x * bpp as usize;
+					let index =
+						(src[bit / 8] >> (8 - bpp as usize - bit % 8)) & ((1 << bpp) - 1) as u8;
+					*palette.get(index as usize).ok_or(WaylandError::InvalidImage)?
+				}
+				24 => {
+					0xff000000
+						| (src[x * 3 + 2] as u32) << 16
+						| (src[x * 3 + 1] as u32) << 8
+						| src[x * 3] as u32
+				}
+				_ => {
+					let v = if bpp == 16 {
+						u16::from_le_bytes([src[x * 2], src[x * 2 + 1]]) as u32
+					} else {
+						u32::from_le_bytes([
+							src[x * 4],
+							src[x * 4 + 1],
+							src[x * 4 + 2],
+							src[x * 4 + 3],
+						])
+					};
+					let [r, g, b] = [0, 1, 2].map(|ix| masked(v, masks[ix]).unwrap_or(0));
+					let a = masked(v, masks[3]).unwrap_or(255);
+					any_alpha |= a != 0;
+					(a as u32) << 24 | (r as u32) << 16 | (g as u32) << 8 | b as u32
+				}
+			};
+			image.pixels[y * w + x] = px;
+		}
+	}
+	// plenty of writers set an alpha mask and then leave it all zero
+	for px in image.pixels.iter_mut() {
+		let argb = if any_alpha || masks[3] == 0 {
+			*px
+		} else {
+			*px | 0xff000000
+		};
+		*px = Color::from_argb(argb).premultiplied();
+	}
+	Ok(image)
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+	let p = a as i16 + b as i16 - c as i16;
+	let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+	if pa <= pb && pa <= pc {
+		a
+	} else if pb <= pc {
+		b
+	} else {
+		c
+	}
+}
+
+// undoes the per row filters in place, rows keep their filter byte
+fn unfilter(data: &mut [u8], row_len: usize, bpp: usize) -> Result<(), WaylandError> {
+	let mut prev = vec![0u8; row_len];
+	for row in data.chunks_exact_mut(row_len + 1) {
+		let (filter, row) = row.split_first_mut().ok_or(WaylandError::InvalidImage)?;
+		for ix in 0..row_len {
+			let left = if ix >= bpp {
+				row[ix - bpp]
+			} else {
+				0
+			};
+			let up_left = if ix >= bpp {
+				prev[ix - bpp]
+			} else {
+				0
+			};
+			row[ix] = row[ix].wrapping_add(match filter {
+				0 => 0,
+				1 => left,
+				2 => prev[ix],
+				3 => ((left as u16 + prev[ix] as u16) / 2) as u8,
+				4 => paeth(left, prev[ix], up_left),
+				_ => return Err(WaylandError::InvalidImage),
+			});
+		}
+		prev.copy_from_slice(row);
+	}
+	Ok(())
+}
+
+fn sample(row: &[u8], depth: usize, index: usize) -> u16 {
+	match depth {
+		8 => row[index] as u16,
+		16 => u16::from_be_bytes([row[index * 2], row[index * 2 + 1]]),
+		_ => {
+			let bit = index * depth;
+			((row[bit / 8] >> (8 - depth - bit % 8)) & ((1 << depth) - 1) as u8) as u16
+		}
+	}
+}
+
+// every color type and bit depth, interlaced or not. no gamma or color management
+pub fn decode_png(data: &[u8]) -> Result<Pixmap, WaylandError> {
+	if !data.starts_with(&PNG_MAGIC) {
+		return Err(WaylandError::InvalidImage);
+	}
+	let mut header = None;
+	let (mut palette, mut trns, mut idat) = (vec![], vec![], vec![]);
+	let mut at = PNG_MAGIC.len();
+	while let Some(chunk) = data.get(at..at + 8) {
+		let len = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) as usize;
+		let body = data.get(at + 8..at + 8 + len).ok_or(WaylandError::InvalidImage)?;
+		match &chunk[4..8] {
+			b"IHDR" => header = Some(body.to_vec()),
+			b"PLTE" => palette = body.to_vec(),
+			b"tRNS" => trns = body.to_vec(),
+			b"IDAT" => idat.extend_from_slice(body),
+			b"IEND" => break,
+			_ => (),
+		}
+		// length, type, body and crc
+		at += 12 + len;
+	}
+	let header = header.filter(|h| h.len() >= 13).ok_or(WaylandError::InvalidImage)?;
+	let be32 = |b: &[u8]| u32::from_be_bytes([b[0], b[1], b[2], b[3]]);
+	let mut image = new_pixmap(be32(&header[0..4]) as u64, be32(&header[4..8]) as u64)?;
+	let (depth, color_type, interlaced) = (header[8] as usize, header[9], header[12] == 1);
+	let channels = match (color_type, depth) {
+		(0, 1 | 2 | 4 | 8 | 16) => 1,
+		(3, 1 | 2 | 4 | 8) => 1,
+		(2, 8 | 16) => 3,
+		(4, 8 | 16) => 2,
+		(6, 8 | 16) => 4,
+		_ => return Err(WaylandError::InvalidImage),
+	};
+
+	let (w, h) = (image.width as usize, image.height as usize);
+	let bpp = (channels * depth).div_ceil(8);
+	let passes: &[_] = if interlaced {
+		&ADAM7
+	} else {
+		&[(0, 0, 1, 1)]
+	};
+	// (width, height, row length) of a pass, rows also start with their filter byte
+	let pass_sizes = |(x0, y0, dx, dy): (usize, usize, usize, usize)| {
+		let pass_w = (w + dx - 1 - x0) / dx;
+		let pass_h = (h + dy - 1 - y0) / dy;
+		(pass_w, pass_h, (pass_w * channels * depth).div_ceil(8))
+	};
+	let expected = passes
+		.iter()
+		.map(|pass| pass_sizes(*pass))
+		.filter(|(pass_w, pass_h, _)| *pass_w != 0 && *pass_h != 0)
+		.map(|(_, pass_h, row_len)| (row_len + 1) * pass_h)
+		.sum();
+	let mut raw = zlib_decompress(&idat, expected)?;
+
+	let to8 = |v: u16| match depth {
+		16 => (v >> 8) as u8,
+		8 => v as u8,
+		_ => (v as u32 * 255 / ((1 << depth) - 1)) as u8,
+	};
+	// the 16 bit value tRNS marks as transparent for gray and rgb
+	let key = |ix: usize| trns.get(ix * 2..ix * 2 + 2).map(|b| u16::from_be_bytes([b[0], b[1]]));
+
+	let mut offset = 0;
+	for &(x0, y0, dx, dy) in passes {
+		let (pass_w, pass_h, row_len) = pass_sizes((x0, y0, dx, dy));
+		if pass_w == 0 || pass_h == 0 {
+			continue;
+		}
+		let pass = raw
+			.get_mut(offset..offset + (row_len + 1) * pass_h)
+			.ok_or(WaylandError::InvalidImage)?;
+		offset += (row_len + 1) * pass_h;
+		unfilter(pass, row_len, bpp)?;
+
+		for (py, row) in pass.chunks_exact(row_len + 1).enumerate() {
+			let row = &row[1..];
+			for px in 0..pass_w {
+				let s = |c: usize| sample(row, depth, px * channels + c);
+				let argb = match color_type {
+					0 => {
+						let v = to8(s(0));
+						let a = if key(0) == Some(s(0)) {
+							0
+						} else {
+							255
+						};
+						rgba(v, v, v, a)
+					}
+					2 => {
+						let a = if (0..3).all(|c| key(c) == Some(s(c))) {
+							0
+						} else {
+							255
+						};
+						rgba(to8(s(0)), to8(s(1)), to8(s(2)), a)
+					}
+					3 => {
+						let ix = s(0) as usize;
+						let rgb =
+							palette.get(ix * 3..ix * 3 + 3).ok_or(WaylandError::InvalidImage)?;
+						rgba(rgb[0], rgb[1], rgb[2], trns.get(ix).copied().unwrap_or(255))
+					}
+					4 => {
+						let v = to8(s(0));
+						rgba(v, v, v, to8(s(1)))
+					}
+					_ => rgba(to8(s(0)), to8(s(1)), to8(s(2)), to8(s(3))),
+				};
+				image.pixels[(y0 + py * dy) * w + x0 + px * dx] = argb;
+			}
+		}
+	}
+	Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs;
+
+	use super::*;
+
+	// 7x5, expected straight rgba next to each image as "r,g,b,a" per pixel, rows on lines
+	fn check(name: &str, expect: &str) {
+		let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/image/");
+		let image = load(format!("{dir}{name}")).unwrap();
+		let text = fs::read_to_string(format!("{dir}{expect}")).unwrap();
+		let rows: Vec<&str> = text.lines().collect();
+		assert_eq!((image.width as usize, image.height as usize), (7, rows.len()));
+		let expected: Vec<u32> = rows
+			.iter()
+			.flat_map(|row| row.split(' '))
+			.map(|px| {
+				let c: Vec<u8> = px.split(',').map(|c| c.parse().unwrap()).collect();
+				rgba(c[0], c[1], c[2], c[3])
+			})
+			.collect();
+		assert_eq!(image.pixels, expected, "{name}");
+	}
+
+	#[test]
+	fn ppm_and_pam() {
+		check("p3.ppm", "p3.txt");
+		check("p6.ppm", "p6.txt");
+		check("p7.pam", "p7.txt");
+	}
+
+	#[test]
+	fn qoi() {
+		check("q.qoi", "q.txt");
+	}
+
+	#[test]
+	fn bmp() {
+		// 24 bit bottom up, 32 bit top down with bitfields
+		check("b24.bmp", "b24.txt");
+		check("b32.bmp", "b32.txt");
+	}
+
+	#[test]
+	fn png() {
+		check("rgba8.png", "rgba8.txt");
+		check("ga8.png", "ga8.txt");
+		check("gray2.png", "gray2.txt");
+		check("rgbtrns.png", "rgbtrns.txt");
+		check("pal4.png", "pal4.txt");
+	}
+
+	#[test]
+	fn png_interlaced() {
+		check("rgba8i.png", "rgba8i.txt");
+		check("rgb16i.png", "rgb16i.txt");
+		check("gray1i.png", "gray1i.txt");
+	}
+
+	#[test]
+	fn truncated_png() {
+		let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/image/");
+		let data = fs::read(format!("{dir}rgba8.png")).unwrap();
+		assert!(decode(&data[..data.len() / 2]).is_err());
+	}
+}
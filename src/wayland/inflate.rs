@@ -0,0 +1,278 @@
+use crate::wayland::WaylandError;
+
+const LEN_BASE: [u16; 29] = [
+	3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+	163, 195, 227, 258,
+];
+const LEN_EXTRA: [u8; 29] =
+	[0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DIST_BASE: [u16; 30] = [
+	1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+	2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+	0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+	13,
+];
+// order the code length code lengths come in
+const CLEN_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+struct BitReader<'a> {
+	data: &'a [u8],
+	pos: usize,
+	bit: u32,
+}
+
+impl BitReader<'_> {
+	fn bits(&mut self, n: u32) -> Result<u32, WaylandError> {
+		let mut out = 0;
+		for ix in 0..n {
+			let byte = *self.data.get(self.pos).ok_or(WaylandError::InvalidImage)?;
+			out |= ((byte >> self.bit) as u32 & 1) << ix;
+			self.bit += 1;
+			if self.bit == 8 {
+				self.bit = 0;
+				self.pos += 1;
+			}
+		}
+		Ok(out)
+	}
+
+	fn align(&mut self) {
+		if self.bit != 0 {
+			self.bit = 0;
+			self.pos += 1;
+		}
+	}
+}
+
+// canonical huffman code as counts per length and symbols sorted by code
+struct Huffman {
+	counts: [u16; 16],
+	symbols: Vec<u16>,
+}
+
+impl Huffman {
+	fn new(lengths: &[u8]) -> Self {
+		let mut counts = [0; 16];
+		for len in lengths {
+			counts[*len as usize] += 1;
+		}
+		counts[0] = 0;
+		let mut offsets = [0; 16];
+		for len in 1..15 {
+			offsets[len + 1] = offsets[len] + counts[len];
+		}
+		let mut symbols = vec![0; lengths.len()];
+		for (symbol, len) in lengths.iter().enumerate().filter(|(_, len)| **len != 0) {
+			symbols[offsets[*len as usize] as usize] = symbol as u16;
+			offsets[*len as usize] += 1;
+		}
+		Self {
+			counts,
+			symbols,
+		}
+	}
+
+	fn decode(&self, br: &mut BitReader) -> Result<u16, WaylandError> {
+		let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+		for len in 1..16 {
+			code |= br.bits(1)? as i32;
+			let count = self.counts[len] as i32;
+			if code - first < count {
+				return Ok(self.symbols[(index + code - first) as usize]);
+			}
+			index += count;
+			first = (first + count) << 1;
+			code <<= 1;
+		}
+		Err(WaylandError::InvalidImage)
+	}
+}
+
+fn fixed_tables() -> (Huffman, Huffman) {
+	let mut lengths = [0u8; 288];
+	lengths[..144].fill(8);
+	lengths[144..256].fill(9);
+	lengths[256..280].fill(7);
+	lengths[280..].fill(8);
+	(Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_tables(br: &mut BitReader) -> Result<(Huffman, Huffman), WaylandError> {
+	let nlen = br.bits(5)? as usize + 257;
+	let ndist = br.bits(5)? as usize + 1;
+	let ncode = br.bits(4)? as usize + 4;
+	let mut clens = [0u8; 19];
+	for ix in CLEN_ORDER.iter().take(ncode) {
+		clens[*ix] = br.bits(3)? as u8;
+	}
+	let clen = Huffman::new(&clens);
+
+	let mut lengths = vec![];
+	while lengths.len() < nlen + ndist {
+		let (len, repeat) = match clen.decode(br)? {
+			sym @ 0..=15 => (sym as u8, 1),
+			16 => (*lengths.last().ok_or(WaylandError::InvalidImage)?, 3 + br.bits(2)?),
+			17 => (0, 3 + br.bits(3)?),
+			18 => (0, 11 + br.bits(7)?),
+			_ => return Err(WaylandError::InvalidImage),
+		};
+		lengths.extend((0..repeat).map(|_| len));
+	}
+	if lengths.len() != nlen + ndist || lengths[256] == 0 {
+		return Err(WaylandError::InvalidImage);
+	}
+	Ok((Huffman::new(&lengths[..nlen]), Huffman::new(&lengths[nlen..])))
+}
+
+fn inflate_block(
+	br: &mut BitReader,
+	out: &mut Vec<u8>,
+	limit: usize,
+	(lit, dist): &(Huffman, Huffman),
+) -> Result<(), WaylandError> {
+	loop {
+		let sym = lit.decode(br)? as usize;
+		match sym {
+			0..=255 if out.len() < limit => out.push(sym as u8),
+			0..=255 => return Err(WaylandError::InvalidImage),
+			256 => return Ok(()),
+			_ => {
+				let ix = sym - 257;
+				let len = *LEN_BASE.get(ix).ok_or(WaylandError::InvalidImage)? as usize
+					+ br.bits(LEN_EXTRA[ix] as u32)? as usize;
+				let ix = dist.decode(br)? as usize;
+				let back = *DIST_BASE.get(ix).ok_or(WaylandError::InvalidImage)? as usize
+					+ br.bits(DIST_EXTRA[ix] as u32)? as usize;
+				if back > out.len() || out.len() + len > limit {
+					return Err(WaylandError::InvalidImage);
+				}
+				// the copy can overlap what it writes
+				let start = out.len() - back;
+				for at in start..start + len {
+					out.push(out[at]);
+				}
+			}
+		}
+	}
+}
+
+// raw deflate stream, rfc 1951. more than limit bytes of output is an error,
+// a few bytes of input can otherwise expand to gigabytes
+pub fn inflate(data: &[u8], limit: usize) -> Result<Vec<u8>, WaylandError> {
+	let mut br = BitReader {
+		data,
+		pos: 0,
+		bit: 0,
+	};
+	let mut out = vec![];
+	loop {
+		let last = br.bits(1)? == 1;
+		match br.bits(2)? {
+			// stored
+			0 => {
+				br.align();
+				let header = data.get(br.pos..br.pos + 4).ok_or(WaylandError::InvalidImage)?;
+				let len = u16::from_le_bytes([header[0], header[1]]);
+				let nlen = u16::from_le_bytes([header[2], header[3]]);
+				if len != !nlen {
+					return Err(WaylandError::InvalidImage);
+				}
+				let start = br.pos + 4;
+				if out.len() + len as usize > limit {
+					return Err(WaylandError::InvalidImage);
+				}
+				out.extend(
+					data.get(start..start + len as usize).ok_or(WaylandError::InvalidImage)?,
+				);
+				br.pos = start + len as usize;
+			}
+			1 => inflate_block(&mut br, &mut out, limit, &fixed_tables())?,
+			2 => {
+				let tables = dynamic_tables(&mut br)?;
+				inflate_block(&mut br, &mut out, limit, &tables)?
+			}
+			_ => return Err(WaylandError::InvalidImage),
+		}
+		if last {
+			return Ok(out);
+		}
+	}
+}
+
+fn adler32(data: &[u8]) -> u32 {
+	let (mut a, mut b) = (1u32, 0u32);
+	for chunk in data.chunks(5552) {
+		for byte in chunk {
+			a += *byte as u32;
+			b += a;
+		}
+		a %= 65521;
+		b %= 65521;
+	}
+	b << 16 | a
+}
+
+// zlib wrapped deflate, rfc 1950
+pub fn zlib_decompress(data: &[u8], limit: usize) -> Result<Vec<u8>, WaylandError> {
+	let [cmf, flg, ..] = *data else {
+		return Err(WaylandError::InvalidImage);
+	};
+	// deflate, no preset dictionary
+	if cmf & 0x0f != 8 || !(cmf as u16 * 256 + flg as u16).is_multiple_of(31) || flg & 0x20 != 0 {
+		return Err(WaylandError::InvalidImage);
+	}
+	let out = inflate(&data[2..], limit)?;
+	if let Some(sum) = data.len().checked_sub(4).and_then(|at| data.get(at..))
+		&& u32::from_be_bytes([sum[0], sum[1], sum[2], sum[3]]) != adler32(&out)
+	{
+		return Err(WaylandError::InvalidImage);
+	}
+	Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs;
+
+	use super::*;
+
+	const LIMIT: usize = 1 << 20;
+
+	fn fixture(name: &str) -> Vec<u8> {
+		let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/inflate/");
+		fs::read(format!("{dir}{name}")).unwrap()
+	}
+
+	#[test]
+	fn stored_blocks() {
+		assert_eq!(zlib_decompress(&fixture("stored.zlib"), LIMIT).unwrap(), fixture("text.txt"));
+	}
+
+	#[test]
+	fn fixed_huffman() {
+		assert_eq!(zlib_decompress(&fixture("fixed.zlib"), LIMIT).unwrap(), fixture("text.txt"));
+	}
+
+	#[test]
+	fn dynamic_huffman() {
+		assert_eq!(zlib_decompress(&fixture("dynamic.zlib"), LIMIT).unwrap(), fixture("text.txt"));
+	}
+
+	#[test]
+	fn bad_adler32() {
+		let mut data = fixture("dynamic.zlib");
+		*data.last_mut().unwrap() ^= 1;
+		assert!(zlib_decompress(&data, LIMIT).is_err());
+	}
+
+	#[test]
+	fn output_limit() {
+		let len = fixture("text.txt").len();
+		for name in ["stored.zlib", "fixed.zlib", "dynamic.zlib"] {
+			assert!(zlib_decompress(&fixture(name), len).is_ok());
+			assert!(zlib_decompress(&fixture(name), len - 1).is_err());
+		}
+	}
+}
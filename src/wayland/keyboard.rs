@@ -0,0 +1,134 @@
+use std::{
+	error::Error,
+	os::fd::{FromRawFd, OwnedFd},
+};
+
+use crate::wayland::{
+	CtxType, DebugLevel, EventAction, WaylandError, WaylandObject, WaylandObjectKind,
+	wire::{FdQueue, FromWirePayload, Id, WireRequest},
+};
+
+pub struct Keyboard {
+	pub id: Id,
+	ctx: CtxType,
+	pub(crate) version: u32,
+	fds: FdQueue,
+	// of the latest input event, selections and popups want one
+	pub serial: Option<u32>,
+	pub focus: Option<Id>,
+	// evdev key codes
+	pub pressed: Vec<u32>,
+	// depressed, latched, locked and group, as xkb masks
+	pub modifiers: (u32, u32, u32, u32),
+	// keys per second and delay in ms, a rate of 0 means no repeat
+	pub repeat: Option<(i32, i32)>,
+	// xkb v1 keymap to be mapped read only, and its size
+	pub keymap: Option<(OwnedFd, u32)>,
+}
+
+impl Keyboard {
+	pub(crate) fn new(id: Id, ctx: CtxType, version: u32) -> Self {
+		let fds = ctx.borrow().wlmm.fds.clone();
+		Self {
+			id,
+			ctx,
+			version,
+			fds,
+			serial: None,
+			focus: None,
+			pressed: vec![],
+			modifiers: (0, 0, 0, 0),
+			repeat: None,
+			keymap: None,
+		}
+	}
+
+	pub(crate) fn wl_release(&self) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 0,
+			args: vec![],
+		})
+	}
+
+	pub fn release(&self) -> Result<(), Box<dyn Error>> {
+		// release only exists since v3
+		if self.version >= 3 {
+			self.ctx.borrow().wlmm.send_request(&mut self.wl_release()?)?;
+		}
+		self.ctx.borrow_mut().wlim.free_id(self.id)?;
+		Ok(())
+	}
+}
+
+impl WaylandObject for Keyboard {
+	fn handle(
+		&mut self,
+		opcode: super::OpCode,
+		payload: &[u8],
+	) -> Result<Vec<EventAction>, Box<dyn Error>> {
+		let mut pending = vec![];
+		let p = payload;
+		match opcode {
+			// keymap
+			0 => {
+				let fd = self.fds.borrow_mut().pop_front().ok_or(WaylandError::MissingFd)?;
+				let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+				let format = u32::from_wire(p)?;
+				let size = u32::from_wire(&p[4..])?;
+				// 0 is no_keymap, the fd is still sent along
+				self.keymap = (format == 1).then_some((fd, size));
+			}
+			// enter
+			1 => {
+				let serial = u32::from_wire(p)?;
+				let surface = u32::from_wire(&p[4..])?;
+				self.serial = Some(serial);
+				self.focus = Some(surface);
+				self.pressed = Vec::from_wire(&p[8..])?;
+				pending.push(EventAction::DebugMessage(
+					DebugLevel::Verbose,
+					format!("{} entered surface {}", self.as_str(), surface),
+				));
+			}
+			// leave
+			2 => {
+				self.serial = Some(u32::from_wire(p)?);
+				self.focus = None;
+				self.pressed.clear();
+			}
+			// key
+			3 => {
+				self.serial = Some(u32::from_wire(p)?);
+				let key = u32::from_wire(&p[8..])?;
+				let state = u32::from_wire(&p[12..])?;
+				// 2 is a compositor side repeat of a key that's already down
+				match state {
+					0 => self.pressed.retain(|k| *k != key),
+					1 => self.pressed.push(key),
+					_ => {}
+				}
+			}
+			// modifiers
+			4 => {
+				self.serial = Some(u32::from_wire(p)?);
+				self.modifiers = (
+					u32::from_wire(&p[4..])?,
+					u32::from_wire(&p[8..])?,
+					u32::from_wire(&p[12..])?,
+					u32::from_wire(&p[16..])?,
+				);
+			}
+			// repeat_info
+			5 => {
+				self.repeat = Some((i32::from_wire(p)?, i32::from_wire(&p[4..])?));
+			}
+			inv => return Err(WaylandError::InvalidOpCode(inv, self.as_str()).boxed()),
+		}
+		Ok(pending)
+	}
+
+	fn as_str(&self) -> &'static str {
+		WaylandObjectKind::Keyboard.as_str()
+	}
+}
@@ -0,0 +1,342 @@
+use std::{cell::RefCell, error::Error, rc::Rc};
+
+use crate::wayland::{
+	CtxType, DebugLevel, EventAction, RcCell, WaylandError, WaylandObject, WaylandObjectKind,
+	output::Output,
+	registry::Registry,
+	surface::Surface,
+	wire::{FromWirePayload, Id, WireArgument, WireRequest},
+};
+
+// stacking order, bottom to top. windows go between bottom and top
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layer {
+	Background = 0,
+	Bottom,
+	Top,
+	Overlay,
+}
+
+// a bitmask of these, anchoring to opposite edges stretches the surface between them
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anchor {
+	Top = 1,
+	Bottom = 2,
+	Left = 4,
+	Right = 8,
+}
+
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyboardInteractivity {
+	None = 0,
+	// takes all keyboard input while on top or overlay, for lock screens and launchers
+	Exclusive,
+	// focused like a window when clicked, needs v4
+	OnDemand,
+}
+
+pub struct LayerShell {
+	pub id: Id,
+	ctx: CtxType,
+	pub(crate) version: u32,
+}
+
+impl LayerShell {
+	pub fn new_bound(registry: &mut Registry) -> Result<RcCell<Self>, Box<dyn Error>> {
+		let version = registry
+			.does_implement(WaylandObjectKind::LayerShell.as_str())
+			.ok_or(WaylandError::NotInRegistry)?
+			.min(4);
+		let obj = Rc::new(RefCell::new(Self {
+			id: 0,
+			ctx: registry.ctx.clone(),
+			version,
+		}));
+		let id = registry
+			.ctx
+			.borrow_mut()
+			.wlim
+			.new_id_registered(WaylandObjectKind::LayerShell, obj.clone());
+		obj.borrow_mut().id = id;
+		registry.bind(id, WaylandObjectKind::LayerShell, version)?;
+		Ok(obj)
+	}
+
+	pub(crate) fn wl_destroy(&self) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 1,
+			args: vec![],
+		})
+	}
+
+	pub fn destroy(&self) -> Result<(), Box<dyn Error>> {
+		// destroy only exists since v3
+		if self.version >= 3 {
+			self.ctx.borrow().wlmm.send_request(&mut self.wl_destroy()?)?;
+		}
+		self.ctx.borrow_mut().wlim.free_id(self.id)?;
+		Ok(())
+	}
+
+	pub(crate) fn wl_get_layer_surface(
+		&self,
+		id: Id,
+		wl_surface_id: Id,
+		output_id: Id,
+		layer: Layer,
+		namespace: &str,
+	) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 0,
+			args: vec![
+				WireArgument::NewId(id),
+				WireArgument::Obj(wl_surface_id),
+				WireArgument::Obj(output_id),
+				WireArgument::UnInt(layer as u32),
+				WireArgument::String(namespace.to_string()),
+			],
+		})
+	}
+
+	// output None lets the compositor pick, usually the focused one. namespace says what
+	// the surface is for, like "panel" or "wallpaper". set the rest up before the first commit,
+	// a size of 0 on an axis needs anchors on both of its edges
+	pub fn make_layer_surface(
+		&self,
+		wl_surface: RcCell<Surface>,
+		output: Option<&Output>,
+		layer: Layer,
+		namespace: &str,
+		(w, h): (i32, i32),
+	) -> Result<RcCell<LayerSurface>, Box<dyn Error>> {
+		let surf_id = wl_surface.borrow().id;
+		let output_id = output.map_or(0, |output| output.id);
+		let layer_surface = Rc::new(RefCell::new(LayerSurface {
+			id: 0,
+			ctx: self.ctx.clone(),
+			version: self.version,
+			is_configured: false,
+			closed: false,
+			wl_surface,
+			w,
+			h,
+			layer,
+		}));
+		let id = {
+			let mut ctx = self.ctx.borrow_mut();
+			let id =
+				ctx.wlim.new_id_registered(WaylandObjectKind::LayerSurface, layer_surface.clone());
+			ctx.wlmm.send_request(
+				&mut self.wl_get_layer_surface(id, surf_id, output_id, layer, namespace)?,
+			)?;
+			id
+		};
+		layer_surface.borrow_mut().id = id;
+		layer_surface.borrow().set_size((w, h))?;
+		Ok(layer_surface)
+	}
+}
+
+pub struct LayerSurface {
+	pub id: Id,
+	ctx: CtxType,
+	version: u32,
+	pub is_configured: bool,
+	// the compositor took the surface away, like when its output went away. destroy it
+	pub closed: bool,
+	pub(crate) wl_surface: RcCell<Surface>,
+	pub(crate) w: i32,
+	pub(crate) h: i32,
+	pub layer: Layer,
+}
+
+impl LayerSurface {
+	pub(crate) fn wl_set_size(&self, (w, h): (i32, i32)) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 0,
+			args: vec![WireArgument::UnInt(w as u32), WireArgument::UnInt(h as u32)],
+		})
+	}
+
+	// applied on the next commit, the compositor answers with a configure
+	pub fn set_size(&self, size: (i32, i32)) -> Result<(), Box<dyn Error>> {
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_set_size(size)?)
+	}
+
+	pub(crate) fn wl_set_anchor(&self, anchor: u32) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 1,
+			args: vec![WireArgument::UnInt(anchor)],
+		})
+	}
+
+	// an Anchor mask, 0 centers the surface
+	pub fn set_anchor(&self, anchor: u32) -> Result<(), Box<dyn Error>> {
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_set_anchor(anchor)?)
+	}
+
+	pub(crate) fn wl_set_exclusive_zone(&self, zone: i32) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 2,
+			args: vec![WireArgument::Int(zone)],
+		})
+	}
+
+	// how much of the anchored edge to keep other surfaces away from, like a panel does.
+	// 0 moves out of the way of other zones, -1 ignores them and goes right to the edge
+	pub fn set_exclusive_zone(&self, zone: i32) -> Result<(), Box<dyn Error>> {
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_set_exclusive_zone(zone)?)
+	}
+
+	pub(crate) fn wl_set_margin(
+		&self,
+		(top, right, bottom, left): (i32, i32, i32, i32),
+	) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 3,
+			args: vec![
+				WireArgument::Int(top),
+				WireArgument::Int(right),
+				WireArgument::Int(bottom),
+				WireArgument::Int(left),
+			],
+		})
+	}
+
+	// top, right, bottom and left, only the anchored edges use theirs
+	pub fn set_margin(&self, margin: (i32, i32, i32, i32)) -> Result<(), Box<dyn Error>> {
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_set_margin(margin)?)
+	}
+
+	pub(crate) fn wl_set_keyboard_interactivity(
+		&self,
+		interactivity: KeyboardInteractivity,
+	) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 4,
+			args: vec![WireArgument::UnInt(interactivity as u32)],
+		})
+	}
+
+	// on demand falls back to none before v4
+	pub fn set_keyboard_interactivity(
+		&self,
+		interactivity: KeyboardInteractivity,
+	) -> Result<(), Box<dyn Error>> {
+		let interactivity = match interactivity {
+			KeyboardInteractivity::OnDemand if self.version < 4 => KeyboardInteractivity::None,
+			other => other,
+		};
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_set_keyboard_interactivity(interactivity)?)
+	}
+
+	pub(crate) fn wl_ack_configure(&self, serial: u32) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 6,
+			args: vec![WireArgument::UnInt(serial)],
+		})
+	}
+
+	pub(crate) fn wl_destroy(&self) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 7,
+			args: vec![],
+		})
+	}
+
+	// the surface gets unmapped and can be given another role
+	pub fn destroy(&self) -> Result<(), Box<dyn Error>> {
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_destroy()?)?;
+		self.ctx.borrow_mut().wlim.free_id(self.id)?;
+		Ok(())
+	}
+
+	pub(crate) fn wl_set_layer(&self, layer: Layer) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 8,
+			args: vec![WireArgument::UnInt(layer as u32)],
+		})
+	}
+
+	// moving a mapped surface to another layer needs v2, older ones have to remake it
+	pub fn set_layer(&mut self, layer: Layer) -> Result<(), Box<dyn Error>> {
+		if self.version < 2 {
+			return Err(WaylandError::UnsupportedVersion(self.as_str(), 2).boxed());
+		}
+		self.layer = layer;
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_set_layer(layer)?)
+	}
+}
+
+impl WaylandObject for LayerShell {
+	fn handle(
+		&mut self,
+		opcode: super::OpCode,
+		_payload: &[u8],
+	) -> Result<Vec<EventAction>, Box<dyn Error>> {
+		Err(WaylandError::InvalidOpCode(opcode, self.as_str()).boxed())
+	}
+
+	fn as_str(&self) -> &'static str {
+		WaylandObjectKind::LayerShell.as_str()
+	}
+}
+
+impl WaylandObject for LayerSurface {
+	fn handle(
+		&mut self,
+		opcode: super::OpCode,
+		payload: &[u8],
+	) -> Result<Vec<EventAction>, Box<dyn Error>> {
+		let mut pending = vec![];
+		match opcode {
+			// configure
+			0 => {
+				let serial = u32::from_wire(payload)?;
+				let w = u32::from_wire(&payload[4..])? as i32;
+				let h = u32::from_wire(&payload[8..])? as i32;
+				pending.push(EventAction::DebugMessage(
+					DebugLevel::Verbose,
+					format!("{} configure // w: {}, h: {}", self.as_str(), w, h),
+				));
+				self.is_configured = true;
+				// 0 leaves that axis to us
+				if w != 0 {
+					self.w = w;
+				}
+				if h != 0 {
+					self.h = h;
+				}
+				pending.push(EventAction::Request(self.wl_ack_configure(serial)?));
+				pending.push(EventAction::Resize(self.wl_surface.borrow().id, self.w, self.h));
+			}
+			// closed
+			1 => {
+				self.closed = true;
+				pending.push(EventAction::DebugMessage(
+					DebugLevel::Important,
+					format!("{} {} closed by the compositor", self.as_str(), self.id),
+				));
+			}
+			inv => return Err(WaylandError::InvalidOpCode(inv, self.as_str()).boxed()),
+		}
+		Ok(pending)
+	}
+
+	fn as_str(&self) -> &'static str {
+		WaylandObjectKind::LayerSurface.as_str()
+	}
+}
@@ -1,23 +1,48 @@
 use crate::wayland::{
+	output::{Output, OutputInfo, Transform, XdgOutput, XdgOutputManager},
+	registry::Registry,
+	shm::PixelFormat,
+	surface::Surface,
 	wire::{Id, MessageManager, WireRequest},
-	xdgshell::XdgSurface,
 };
 use std::{
 	cell::RefCell,
-	collections::HashMap,
+	collections::{HashMap, VecDeque},
 	error::Error,
 	fmt::{self, Display},
-	rc::Rc,
+	os::fd::{FromRawFd, OwnedFd},
+	rc::{Rc, Weak},
 };
+pub mod activation;
+pub mod allocator;
 pub mod buffer;
 pub mod callback;
+pub mod canvas;
 pub mod compositor;
+pub mod cursor;
+pub mod cursor_shape;
+pub mod data_device;
 pub mod display;
+pub mod font;
+pub mod fractional_scale;
+pub mod image;
+pub mod inflate;
+pub mod keyboard;
+pub mod layer_shell;
+pub mod output;
+pub mod pointer;
+pub mod presentation;
+pub mod primary_selection;
 pub mod region;
 pub mod registry;
+pub mod seat;
 pub mod shm;
+pub mod subcompositor;
 pub mod surface;
+pub mod swapchain;
+pub mod viewporter;
 pub mod wire;
+pub mod xcursor;
 pub mod xdgshell;
 
 pub type OpCode = usize;
@@ -50,12 +75,35 @@ pub(crate) enum DebugLevel {
 	Severe,
 }
 
+// things the app might want to react to, see Context::poll_event
+#[derive(Debug, Clone)]
+pub enum Event {
+	// surface id, output id
+	SurfaceEnter(Id, Id),
+	SurfaceLeave(Id, Id),
+	PreferredBufferScale(Id, i32),
+	PreferredBufferTransform(Id, Transform),
+	// surface id, scale in 120ths
+	PreferredFractionalScale(Id, u32),
+}
+
 pub(crate) enum EventAction {
 	Request(WireRequest),
+	// we destroyed it, events can still arrive until the compositor confirms with delete_id
 	IdDeletion(Id),
+	// wl_display.delete_id
+	IdDeleted(Id),
 	Error(Box<dyn Error>),
 	DebugMessage(DebugLevel, String),
-	Resize(i32, i32),
+	// wl_surface id and the new logical size
+	Resize(Id, i32, i32),
+	// registry id, global name, advertised version
+	BindOutput(Id, u32, u32),
+	BindXdgOutputManager(Id, u32, u32),
+	GlobalRemoved(u32),
+	Event(Event),
+	// an object the compositor made, under the id it picked
+	NewObject(Id, WaylandObjectKind, Wlto),
 }
 
 pub(crate) trait WaylandObject {
@@ -72,7 +120,10 @@ pub type CtxType = Rc<RefCell<Context>>;
 pub struct Context {
 	wlmm: MessageManager,
 	wlim: IdentManager,
-	xdg_surface: Option<RcCell<XdgSurface>>,
+	surfaces: Vec<Weak<RefCell<Surface>>>,
+	outputs: Vec<RcCell<Output>>,
+	xdg_output_manager: Option<RcCell<XdgOutputManager>>,
+	events: VecDeque<Event>,
 }
 
 impl Context {
@@ -80,8 +131,150 @@ impl Context {
 		Self {
 			wlmm,
 			wlim,
-			xdg_surface: None,
+			surfaces: vec![],
+			outputs: vec![],
+			xdg_output_manager: None,
+			events: VecDeque::new(),
+		}
+	}
+
+	pub fn poll_event(&mut self) -> Option<Event> {
+		self.events.pop_front()
+	}
+
+	// every output that got its first done
+	pub fn outputs(&self) -> Vec<OutputInfo> {
+		self.outputs
+			.iter()
+			.map(|o| o.borrow())
+			.filter(|o| o.ready)
+			.map(|o| o.info.clone())
+			.collect()
+	}
+
+	pub fn output_by_id(&self, id: Id) -> Option<OutputInfo> {
+		self.outputs().into_iter().find(|o| o.id == id)
+	}
+
+	// outputs the surface is currently shown on
+	pub fn outputs_of(&self, surface: &Surface) -> Vec<OutputInfo> {
+		surface.outputs.iter().filter_map(|id| self.output_by_id(*id)).collect()
+	}
+
+	pub(crate) fn surface_by_id(&mut self, id: Id) -> Option<RcCell<Surface>> {
+		self.surfaces.retain(|s| s.strong_count() > 0);
+		self.surfaces.iter().filter_map(|s| s.upgrade()).find(|s| s.borrow().id == id)
+	}
+
+	// keeps the buffer at logical size * scale, runs on configure and on output changes
+	fn rescale_surface(&mut self, surf: &RcCell<Surface>) -> Result<(), Box<dyn Error>> {
+		let mut surf = surf.borrow_mut();
+		// fractional scaling goes through the viewport, the buffer itself stays at scale 1
+		let scale = if surf.uses_fractional_scale() {
+			1
+		} else {
+			self.preferred_scale_of(&surf)
+		};
+		// sent along with the commit of a buffer that has the new size
+		surf.buffer_scale = scale;
+		if let (Some(viewport), Some(size)) = (surf.viewport.clone(), surf.logical_size)
+			&& surf.uses_fractional_scale()
+		{
+			let mut viewport = viewport.borrow_mut();
+			if viewport.destination != Some(size) {
+				viewport.destination = Some(size);
+				self.wlmm.send_request(&mut viewport.wl_set_destination(size)?)?;
+			}
 		}
+		surf.resize_buffer_in(self)
+	}
+
+	// what the compositor asked for, otherwise the biggest scale of the outputs it's on
+	pub fn preferred_scale_of(&self, surface: &Surface) -> i32 {
+		surface
+			.preferred_scale
+			.unwrap_or_else(|| self.outputs_of(surface).iter().map(|o| o.scale).max().unwrap_or(1))
+	}
+
+	fn bind_output(
+		&mut self,
+		registry_id: Id,
+		name: u32,
+		version: u32,
+	) -> Result<(), Box<dyn Error>> {
+		let version = version.min(4);
+		let output = Rc::new(RefCell::new(Output::new(0, name, version)));
+		let id = self.wlim.new_id_registered(WaylandObjectKind::Output, output.clone());
+		output.borrow_mut().set_id(id);
+		self.wlmm.send_request(&mut Registry::wl_bind_name(
+			registry_id,
+			name,
+			id,
+			WaylandObjectKind::Output,
+			version,
+		))?;
+		if let Some(manager) = self.xdg_output_manager.clone() {
+			self.make_xdg_output(&manager.borrow(), output.clone())?;
+		}
+		self.outputs.push(output);
+		Ok(())
+	}
+
+	fn bind_xdg_output_manager(
+		&mut self,
+		registry_id: Id,
+		name: u32,
+		version: u32,
+	) -> Result<(), Box<dyn Error>> {
+		let version = version.min(3);
+		let manager = Rc::new(RefCell::new(XdgOutputManager::new(0, version)));
+		let id = self.wlim.new_id_registered(WaylandObjectKind::XdgOutputManager, manager.clone());
+		manager.borrow_mut().id = id;
+		self.wlmm.send_request(&mut Registry::wl_bind_name(
+			registry_id,
+			name,
+			id,
+			WaylandObjectKind::XdgOutputManager,
+			version,
+		))?;
+		for output in self.outputs.clone() {
+			self.make_xdg_output(&manager.borrow(), output)?;
+		}
+		self.xdg_output_manager = Some(manager);
+		Ok(())
+	}
+
+	fn make_xdg_output(
+		&mut self,
+		manager: &XdgOutputManager,
+		output: RcCell<Output>,
+	) -> Result<(), Box<dyn Error>> {
+		let output_id = output.borrow().id;
+		let xdg_output = Rc::new(RefCell::new(XdgOutput::new(0, manager.version, output.clone())));
+		let id = self.wlim.new_id_registered(WaylandObjectKind::XdgOutput, xdg_output.clone());
+		xdg_output.borrow_mut().id = id;
+		self.wlmm.send_request(&mut manager.wl_get_xdg_output(id, output_id)?)?;
+		output.borrow_mut().xdg_output = Some(xdg_output);
+		Ok(())
+	}
+
+	fn remove_output(&mut self, name: u32) -> Result<(), Box<dyn Error>> {
+		let Some(ix) = self.outputs.iter().position(|o| o.borrow().global_name == name) else {
+			return Ok(());
+		};
+		let output = self.outputs.remove(ix);
+		let output = output.borrow();
+		if let Some(xdg_output) = &output.xdg_output {
+			let xdg_output = xdg_output.borrow();
+			self.wlmm.send_request(&mut xdg_output.wl_destroy()?)?;
+			self.wlim.free_id(xdg_output.id)?;
+		}
+		// release only exists since v3
+		if output.version >= 3 {
+			self.wlmm.send_request(&mut output.wl_release()?)?;
+		}
+		self.wlim.free_id(output.id)?;
+		Ok(())
 	}
 
 	pub fn handle_events(&mut self) -> Result<(), Box<dyn Error>> {
@@ -89,37 +282,81 @@ impl Context {
 		while self.wlmm.get_events()? == 0 && retries < 9999 {
 			retries += 1;
 		}
-		let mut actions: Vec<EventAction> = vec![];
+		// actions go right after their event, later events can be for objects they make
 		while let Some(ev) = self.wlmm.q.pop_front() {
-			let obj = self.wlim.find_obj_by_id(ev.recv_id)?;
-			println!("going to handle {:?}", obj.0);
-			let mut x = obj.1.borrow_mut().handle(ev.opcode, &ev.payload)?;
-			actions.append(&mut x);
-		}
-		for act in actions {
-			match act {
-				EventAction::Request(mut msg) => {
-					self.wlmm.send_request(&mut msg)?;
+			// sent before the compositor saw our destroy
+			if let Some(kind) = self.wlim.zombie_kind(ev.recv_id) {
+				println!("ignoring event {} for destroyed object {}", ev.opcode, ev.recv_id);
+				// or the next event with an fd would take this one's
+				for _ in 0..kind.event_fds(ev.opcode) {
+					let fd =
+						self.wlmm.fds.borrow_mut().pop_front().ok_or(WaylandError::MissingFd)?;
+					drop(unsafe { OwnedFd::from_raw_fd(fd) });
 				}
-				EventAction::IdDeletion(id) => {
-					self.wlim.free_id(id)?;
-				}
-				// add colors
-				EventAction::Error(er) => eprintln!("{:?}", er),
-				// add colors
-				EventAction::DebugMessage(_, msg) => println!("{msg}"),
-				EventAction::Resize(w, h) => {
-					let xdgs = self.xdg_surface.clone().ok_or(WaylandError::ObjectNonExistent)?;
-					let xdgs = xdgs.borrow_mut();
-					let surf = xdgs.wl_surface.borrow_mut();
-					let buf = surf.attached_buf.clone().ok_or(WaylandError::ObjectNonExistent)?;
-					let mut buf = buf.borrow_mut();
-					buf.resize((w, h))?;
-				}
-			};
+				continue;
+			}
+			let (kind, obj) = self.wlim.find_obj_by_id(ev.recv_id)?.clone();
+			println!("going to handle {:?}", kind);
+			let actions = obj.borrow_mut().handle(ev.opcode, &ev.payload)?;
+			for act in actions {
+				self.apply_action(act)?;
+			}
 		}
 		Ok(())
 	}
+
+	fn apply_action(&mut self, act: EventAction) -> Result<(), Box<dyn Error>> {
+		match act {
+			EventAction::Request(mut msg) => {
+				self.wlmm.send_request(&mut msg)?;
+			}
+			EventAction::IdDeletion(id) => {
+				self.wlim.free_id(id)?;
+			}
+			EventAction::IdDeleted(id) => {
+				self.wlim.delete_id(id);
+			}
+			EventAction::Error(er) => eprintln!("\x1b[31m{:?}\x1b[0m", er),
+			EventAction::DebugMessage(level, msg) => match level {
+				DebugLevel::Verbose => println!("{msg}"),
+				DebugLevel::Important => println!("\x1b[33m{msg}\x1b[0m"),
+				DebugLevel::Severe => eprintln!("\x1b[31m{msg}\x1b[0m"),
+			},
+			EventAction::Resize(surface_id, w, h) => {
+				let surf = self.surface_by_id(surface_id).ok_or(WaylandError::ObjectNonExistent)?;
+				surf.borrow_mut().logical_size = Some((w, h));
+				self.rescale_surface(&surf)?;
+			}
+			EventAction::BindOutput(registry_id, name, version) => {
+				self.bind_output(registry_id, name, version)?;
+			}
+			EventAction::BindXdgOutputManager(registry_id, name, version) => {
+				self.bind_xdg_output_manager(registry_id, name, version)?;
+			}
+			EventAction::GlobalRemoved(name) => {
+				self.remove_output(name)?;
+			}
+			EventAction::Event(ev) => {
+				// moving to an output with another scale means a new buffer size
+				if let Event::SurfaceEnter(id, _)
+				| Event::SurfaceLeave(id, _)
+				| Event::PreferredBufferScale(id, _)
+				| Event::PreferredFractionalScale(id, _) = ev
+					&& let Some(surf) = self.surface_by_id(id)
+				{
+					if let Event::PreferredFractionalScale(_, scale) = ev {
+						surf.borrow_mut().fractional_scale = Some(scale);
+					}
+					self.rescale_surface(&surf)?;
+				}
+				self.events.push_back(ev);
+			}
+			EventAction::NewObject(id, kind, obj) => {
+				self.wlim.register(id, kind, obj);
+			}
+		};
+		Ok(())
+	}
 }
 
 #[derive(PartialEq, Debug, Clone, Copy)]
@@ -135,10 +372,40 @@ pub enum WaylandObjectKind {
 	XdgWmBase,
 	XdgSurface,
 	XdgTopLevel,
+	Seat,
+	Pointer,
+	CursorShapeManager,
+	CursorShapeDevice,
+	Output,
+	XdgOutputManager,
+	XdgOutput,
+	FractionalScaleManager,
+	FractionalScale,
+	Viewporter,
+	Viewport,
+	Region,
+	Subcompositor,
+	Subsurface,
+	Presentation,
+	PresentationFeedback,
+	Keyboard,
+	DataDeviceManager,
+	DataDevice,
+	DataSource,
+	DataOffer,
+	PrimarySelectionDeviceManager,
+	PrimarySelectionDevice,
+	PrimarySelectionSource,
+	PrimarySelectionOffer,
+	XdgActivation,
+	XdgActivationToken,
+	LayerShell,
+	LayerSurface,
 }
 
 impl WaylandObjectKind {
-	fn as_str(&self) -> &'static str {
+	// the interface name, as the registry advertises it
+	pub fn as_str(&self) -> &'static str {
 		match self {
 			WaylandObjectKind::Display => "wl_display",
 			WaylandObjectKind::Registry => "wl_registry",
@@ -151,6 +418,49 @@ impl WaylandObjectKind {
 			WaylandObjectKind::XdgWmBase => "xdg_wm_base",
 			WaylandObjectKind::XdgSurface => "xdg_surface",
 			WaylandObjectKind::XdgTopLevel => "xdg_toplevel",
+			WaylandObjectKind::Seat => "wl_seat",
+			WaylandObjectKind::Pointer => "wl_pointer",
+			WaylandObjectKind::CursorShapeManager => "wp_cursor_shape_manager_v1",
+			WaylandObjectKind::CursorShapeDevice => "wp_cursor_shape_device_v1",
+			WaylandObjectKind::Output => "wl_output",
+			WaylandObjectKind::XdgOutputManager => "zxdg_output_manager_v1",
+			WaylandObjectKind::XdgOutput => "zxdg_output_v1",
+			WaylandObjectKind::FractionalScaleManager => "wp_fractional_scale_manager_v1",
+			WaylandObjectKind::FractionalScale => "wp_fractional_scale_v1",
+			WaylandObjectKind::Viewporter => "wp_viewporter",
+			WaylandObjectKind::Viewport => "wp_viewport",
+			WaylandObjectKind::Region => "wl_region",
+			WaylandObjectKind::Subcompositor => "wl_subcompositor",
+			WaylandObjectKind::Subsurface => "wl_subsurface",
+			WaylandObjectKind::Presentation => "wp_presentation",
+			WaylandObjectKind::PresentationFeedback => "wp_presentation_feedback",
+			WaylandObjectKind::Keyboard => "wl_keyboard",
+			WaylandObjectKind::DataDeviceManager => "wl_data_device_manager",
+			WaylandObjectKind::DataDevice => "wl_data_device",
+			WaylandObjectKind::DataSource => "wl_data_source",
+			WaylandObjectKind::DataOffer => "wl_data_offer",
+			WaylandObjectKind::PrimarySelectionDeviceManager => {
+				"zwp_primary_selection_device_manager_v1"
+			}
+			WaylandObjectKind::PrimarySelectionDevice => "zwp_primary_selection_device_v1",
+			WaylandObjectKind::PrimarySelectionSource => "zwp_primary_selection_source_v1",
+			WaylandObjectKind::PrimarySelectionOffer => "zwp_primary_selection_offer_v1",
+			WaylandObjectKind::XdgActivation => "xdg_activation_v1",
+			WaylandObjectKind::XdgActivationToken => "xdg_activation_token_v1",
+			WaylandObjectKind::LayerShell => "zwlr_layer_shell_v1",
+			WaylandObjectKind::LayerSurface => "zwlr_layer_surface_v1",
+		}
+	}
+
+	// how many fds come with the event, they're queued apart from the message
+	pub(crate) fn event_fds(&self, opcode: OpCode) -> usize {
+		match (self, opcode) {
+			// keymap
+			(WaylandObjectKind::Keyboard, 0) => 1,
+			// send
+			(WaylandObjectKind::DataSource, 1) => 1,
+			(WaylandObjectKind::PrimarySelectionSource, 0) => 1,
+			_ => 0,
 		}
 	}
 }
@@ -164,6 +474,8 @@ pub struct IdentManager {
 	top_id: Id,
 	free: Vec<Id>,
 	idmap: HashMap<Id, (WaylandObjectKind, Wlto)>,
+	// destroyed by us, but not yet confirmed by delete_id
+	zombies: HashMap<Id, WaylandObjectKind>,
 }
 
 impl IdentManager {
@@ -179,15 +491,35 @@ impl IdentManager {
 		id
 	}
 
+	// for ids the compositor allocated
+	pub(crate) fn register(&mut self, id: Id, kind: WaylandObjectKind, obj: Wlto) {
+		// the compositor doesn't confirm its own ids, it just reuses them
+		self.zombies.remove(&id);
+		self.idmap.insert(id, (kind, obj));
+	}
+
+	// after sending a destructor, the id is a zombie until delete_id comes
 	pub(crate) fn free_id(&mut self, id: Id) -> Result<(), Box<dyn Error>> {
 		let registered = self.idmap.iter().find(|(k, _)| **k == id).map(|(k, _)| k).copied();
 		if let Some(r) = registered {
-			self.idmap.remove(&r).ok_or(WaylandError::IdMapRemovalFail.boxed())?;
+			let (kind, _) = self.idmap.remove(&r).ok_or(WaylandError::IdMapRemovalFail.boxed())?;
+			self.zombies.insert(id, kind);
 		}
-		self.free.push(id);
 		Ok(())
 	}
 
+	// also covers objects the compositor destroyed itself, like callbacks
+	pub(crate) fn delete_id(&mut self, id: Id) {
+		self.idmap.remove(&id);
+		self.zombies.remove(&id);
+		self.free.push(id);
+	}
+
+	// what the id was, if it's a zombie
+	pub(crate) fn zombie_kind(&self, id: Id) -> Option<WaylandObjectKind> {
+		self.zombies.get(&id).copied()
+	}
+
 	// ugh
 	pub(crate) fn find_obj_by_id(
 		&self,
@@ -197,7 +529,7 @@ impl IdentManager {
 			.iter()
 			.find(|(k, _)| **k == id)
 			.map(|(_, v)| v)
-			.ok_or_else(|| WaylandError::ObjectNonExistent)
+			.ok_or(WaylandError::ObjectNonExistent)
 	}
 
 	pub(crate) fn find_obj_kind_by_id(&self, id: Id) -> Result<WaylandObjectKind, WaylandError> {
@@ -205,7 +537,7 @@ impl IdentManager {
 			.iter()
 			.find(|(k, _)| **k == id)
 			.map(|(_, v)| v.0)
-			.ok_or_else(|| WaylandError::ObjectNonExistent)
+			.ok_or(WaylandError::ObjectNonExistent)
 	}
 
 	pub(crate) fn find_obj_by_kind(&self, kind: WaylandObjectKind) -> Result<Wlto, WaylandError> {
@@ -213,7 +545,7 @@ impl IdentManager {
 			.iter()
 			.find(|(_, v)| v.0 == kind)
 			.map(|(_, v)| v.1.clone())
-			.ok_or_else(|| WaylandError::ObjectNonExistent)
+			.ok_or(WaylandError::ObjectNonExistent)
 	}
 }
 
@@ -229,6 +561,18 @@ pub enum WaylandError {
 	NoSerial,
 	InvalidEnumVariant,
 	BufferObjectNotAttached,
+	InvalidXcursor,
+	CursorNotFound,
+	PoolMapped,
+	UnalignedPixels,
+	UnsupportedPixelFormat(PixelFormat),
+	UndrawableFormat(PixelFormat),
+	InvalidFont,
+	InvalidImage,
+	NoPresentationClock,
+	MissingFd,
+	UnsupportedVersion(&'static str, u32),
+	NoPrimarySelection,
 }
 
 impl WaylandError {
@@ -260,6 +604,34 @@ impl fmt::Display for WaylandError {
 			WaylandError::BufferObjectNotAttached => {
 				write!(f, "no buffer rust object had been attached to the surface")
 			}
+			WaylandError::InvalidXcursor => write!(f, "malformed xcursor file"),
+			WaylandError::CursorNotFound => {
+				write!(f, "cursor not found in the theme or any theme it inherits")
+			}
+			WaylandError::PoolMapped => {
+				write!(f, "the shm pool can't be remapped while a buffer of it is mapped")
+			}
+			WaylandError::UnalignedPixels => {
+				write!(f, "buffer memory isn't aligned to whole 32 bit pixels")
+			}
+			WaylandError::UnsupportedPixelFormat(format) => {
+				write!(f, "the compositor didn't advertise {:?}", format)
+			}
+			WaylandError::UndrawableFormat(format) => {
+				write!(f, "the canvas can't draw into {:?} buffers", format)
+			}
+			WaylandError::InvalidFont => write!(f, "malformed psf or bdf font"),
+			WaylandError::InvalidImage => write!(f, "malformed or unsupported image"),
+			WaylandError::MissingFd => write!(f, "an event came without its file descriptor"),
+			WaylandError::UnsupportedVersion(int, version) => {
+				write!(f, "the bound {} is older than the needed v{}", int, version)
+			}
+			WaylandError::NoPresentationClock => {
+				write!(f, "the compositor hasn't sent the presentation clock id yet")
+			}
+			WaylandError::NoPrimarySelection => {
+				write!(f, "the primary selection is unsupported or hasn't been enabled")
+			}
 		}
 	}
 }
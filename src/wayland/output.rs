@@ -0,0 +1,301 @@
+use std::error::Error;
+
+use crate::wayland::{
+	DebugLevel, EventAction, RcCell, WaylandError, WaylandObject, WaylandObjectKind,
+	wire::{FromWirePayload, Id, WireArgument, WireRequest, wire_str_size},
+};
+
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Transform {
+	#[default]
+	Normal,
+	Rotated90,
+	Rotated180,
+	Rotated270,
+	Flipped,
+	Flipped90,
+	Flipped180,
+	Flipped270,
+}
+
+impl Transform {
+	pub(crate) fn from_u32(processee: u32) -> Result<Self, WaylandError> {
+		match processee {
+			0 => Ok(Self::Normal),
+			1 => Ok(Self::Rotated90),
+			2 => Ok(Self::Rotated180),
+			3 => Ok(Self::Rotated270),
+			4 => Ok(Self::Flipped),
+			5 => Ok(Self::Flipped90),
+			6 => Ok(Self::Flipped180),
+			7 => Ok(Self::Flipped270),
+			_ => Err(WaylandError::InvalidEnumVariant),
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputInfo {
+	pub id: Id,
+	pub x: i32,
+	pub y: i32,
+	// mm
+	pub physical_width: i32,
+	pub physical_height: i32,
+	pub subpixel: i32,
+	pub make: String,
+	pub model: String,
+	pub transform: Transform,
+	pub width: i32,
+	pub height: i32,
+	// mHz
+	pub refresh: i32,
+	pub scale: i32,
+	pub name: Option<String>,
+	pub description: Option<String>,
+	// from zxdg_output_v1, in compositor space
+	pub logical_position: Option<(i32, i32)>,
+	pub logical_size: Option<(i32, i32)>,
+}
+
+impl OutputInfo {
+	fn new(id: Id) -> Self {
+		Self {
+			id,
+			x: 0,
+			y: 0,
+			physical_width: 0,
+			physical_height: 0,
+			subpixel: 0,
+			make: String::new(),
+			model: String::new(),
+			transform: Transform::Normal,
+			width: 0,
+			height: 0,
+			refresh: 0,
+			scale: 1,
+			name: None,
+			description: None,
+			logical_position: None,
+			logical_size: None,
+		}
+	}
+}
+
+// owned by the context, which binds and releases outputs as the registry announces them
+pub struct Output {
+	pub id: Id,
+	pub(crate) global_name: u32,
+	pub(crate) version: u32,
+	pending: OutputInfo,
+	// only updated on done, so it never holds half of a change
+	pub info: OutputInfo,
+	pub ready: bool,
+	pub(crate) xdg_output: Option<RcCell<XdgOutput>>,
+}
+
+impl Output {
+	pub(crate) fn new(id: Id, global_name: u32, version: u32) -> Self {
+		Self {
+			id,
+			global_name,
+			version,
+			pending: OutputInfo::new(id),
+			info: OutputInfo::new(id),
+			ready: false,
+			xdg_output: None,
+		}
+	}
+
+	pub(crate) fn set_id(&mut self, id: Id) {
+		self.id = id;
+		self.pending.id = id;
+		self.info.id = id;
+	}
+
+	pub(crate) fn wl_release(&self) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 0,
+			args: vec![],
+		})
+	}
+
+	fn apply_pending(&mut self) {
+		self.info = self.pending.clone();
+		self.ready = true;
+	}
+}
+
+pub struct XdgOutputManager {
+	pub id: Id,
+	pub(crate) version: u32,
+}
+
+impl XdgOutputManager {
+	pub(crate) fn new(id: Id, version: u32) -> Self {
+		Self {
+			id,
+			version,
+		}
+	}
+
+	pub(crate) fn wl_get_xdg_output(
+		&self,
+		id: Id,
+		output_id: Id,
+	) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 1,
+			args: vec![WireArgument::NewId(id), WireArgument::Obj(output_id)],
+		})
+	}
+}
+
+pub struct XdgOutput {
+	pub id: Id,
+	pub(crate) version: u32,
+	pub(crate) output: RcCell<Output>,
+}
+
+impl XdgOutput {
+	pub(crate) fn new(id: Id, version: u32, output: RcCell<Output>) -> Self {
+		Self {
+			id,
+			version,
+			output,
+		}
+	}
+
+	pub(crate) fn wl_destroy(&self) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 0,
+			args: vec![],
+		})
+	}
+}
+
+impl WaylandObject for Output {
+	fn handle(
+		&mut self,
+		opcode: super::OpCode,
+		payload: &[u8],
+	) -> Result<Vec<EventAction>, Box<dyn Error>> {
+		let p = payload;
+		let mut pending = vec![];
+		match opcode {
+			// geometry
+			0 => {
+				self.pending.x = i32::from_wire(p)?;
+				self.pending.y = i32::from_wire(&p[4..])?;
+				self.pending.physical_width = i32::from_wire(&p[8..])?;
+				self.pending.physical_height = i32::from_wire(&p[12..])?;
+				self.pending.subpixel = i32::from_wire(&p[16..])?;
+				let mut at = 20;
+				self.pending.make = String::from_wire(&p[at..])?;
+				at += wire_str_size(&p[at..])?;
+				self.pending.model = String::from_wire(&p[at..])?;
+				at += wire_str_size(&p[at..])?;
+				self.pending.transform = Transform::from_u32(u32::from_wire(&p[at..])?)?;
+			}
+			// mode
+			1 => {
+				let flags = u32::from_wire(p)?;
+				// only the current mode is interesting
+				if flags & 0x1 != 0 {
+					self.pending.width = i32::from_wire(&p[4..])?;
+					self.pending.height = i32::from_wire(&p[8..])?;
+					self.pending.refresh = i32::from_wire(&p[12..])?;
+				}
+			}
+			// done
+			2 => {
+				self.apply_pending();
+				pending.push(EventAction::DebugMessage(
+					DebugLevel::Verbose,
+					format!("{} {} done: {:?}", self.as_str(), self.id, self.info),
+				));
+			}
+			// scale
+			3 => {
+				self.pending.scale = i32::from_wire(p)?;
+			}
+			// name
+			4 => {
+				self.pending.name = Some(String::from_wire(p)?);
+			}
+			// description
+			5 => {
+				self.pending.description = Some(String::from_wire(p)?);
+			}
+			inv => return Err(WaylandError::InvalidOpCode(inv, self.as_str()).boxed()),
+		}
+		// done only exists since v2, before that every event applies on its own
+		if self.version < 2 {
+			self.apply_pending();
+		}
+		Ok(pending)
+	}
+
+	fn as_str(&self) -> &'static str {
+		WaylandObjectKind::Output.as_str()
+	}
+}
+
+impl WaylandObject for XdgOutputManager {
+	fn handle(
+		&mut self,
+		opcode: super::OpCode,
+		_payload: &[u8],
+	) -> Result<Vec<EventAction>, Box<dyn Error>> {
+		Err(WaylandError::InvalidOpCode(opcode, self.as_str()).boxed())
+	}
+
+	fn as_str(&self) -> &'static str {
+		WaylandObjectKind::XdgOutputManager.as_str()
+	}
+}
+
+impl WaylandObject for XdgOutput {
+	fn handle(
+		&mut self,
+		opcode: super::OpCode,
+		payload: &[u8],
+	) -> Result<Vec<EventAction>, Box<dyn Error>> {
+		let p = payload;
+		let mut output = self.output.borrow_mut();
+		match opcode {
+			// logical_position
+			0 => {
+				output.pending.logical_position =
+					Some((i32::from_wire(p)?, i32::from_wire(&p[4..])?));
+			}
+			// logical_size
+			1 => {
+				output.pending.logical_size = Some((i32::from_wire(p)?, i32::from_wire(&p[4..])?));
+			}
+			// done, deprecated since v3 where wl_output.done covers it
+			2 => {
+				if self.version < 3 {
+					output.apply_pending();
+				}
+			}
+			// name and description, wl_output v4 has them too
+			3 => {
+				output.pending.name = Some(String::from_wire(p)?);
+			}
+			4 => {
+				output.pending.description = Some(String::from_wire(p)?);
+			}
+			inv => return Err(WaylandError::InvalidOpCode(inv, self.as_str()).boxed()),
+		}
+		Ok(vec![])
+	}
+
+	fn as_str(&self) -> &'static str {
+		WaylandObjectKind::XdgOutput.as_str()
+	}
+}
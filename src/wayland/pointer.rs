@@ -0,0 +1,220 @@
+use std::error::Error;
+
+use crate::wayland::{
+	CtxType, DebugLevel, EventAction, RcCell, WaylandError, WaylandObject, WaylandObjectKind,
+	cursor::CursorSurface,
+	cursor_shape::{CursorShape, CursorShapeDevice, CursorShapeManager},
+	registry::Registry,
+	wire::{Fixed, FromWirePayload, Id, WireArgument, WireRequest},
+};
+
+pub struct Pointer {
+	pub id: Id,
+	ctx: CtxType,
+	pub(crate) version: u32,
+	// set_cursor wants the serial of the latest enter
+	pub(crate) enter_serial: Option<u32>,
+	// of the latest button press, drags start from one
+	pub button_serial: Option<u32>,
+	pub focus: Option<Id>,
+	pub position: (f64, f64),
+	pub pressed: Vec<u32>,
+	pub(crate) cursor: Option<RcCell<CursorSurface>>,
+	shape_manager: Option<RcCell<CursorShapeManager>>,
+	shape_device: Option<RcCell<CursorShapeDevice>>,
+	pub shape: Option<CursorShape>,
+}
+
+impl Pointer {
+	pub(crate) fn new(id: Id, ctx: CtxType, version: u32) -> Self {
+		Self {
+			id,
+			ctx,
+			version,
+			enter_serial: None,
+			button_serial: None,
+			focus: None,
+			position: (0.0, 0.0),
+			pressed: vec![],
+			cursor: None,
+			shape_manager: None,
+			shape_device: None,
+			shape: None,
+		}
+	}
+
+	pub(crate) fn wl_set_cursor(
+		&self,
+		serial: u32,
+		surface_id: Id,
+		(hotspot_x, hotspot_y): (i32, i32),
+	) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 0,
+			args: vec![
+				WireArgument::UnInt(serial),
+				WireArgument::Obj(surface_id),
+				WireArgument::Int(hotspot_x),
+				WireArgument::Int(hotspot_y),
+			],
+		})
+	}
+
+	pub(crate) fn wl_release(&self) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 1,
+			args: vec![],
+		})
+	}
+
+	pub fn release(&self) -> Result<(), Box<dyn Error>> {
+		// release only exists since v3
+		if self.version >= 3 {
+			self.ctx.borrow().wlmm.send_request(&mut self.wl_release()?)?;
+		}
+		self.ctx.borrow_mut().wlim.free_id(self.id)?;
+		Ok(())
+	}
+
+	// the cursor surface gets shown on every pointer enter from now on
+	pub fn set_cursor_surface(
+		&mut self,
+		cursor: RcCell<CursorSurface>,
+	) -> Result<(), Box<dyn Error>> {
+		self.cursor = Some(cursor);
+		self.apply_cursor()
+	}
+
+	// switches the cursor image, pointer has to have a cursor surface set
+	pub fn set_cursor(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+		let cursor = self.cursor.clone().ok_or(WaylandError::ObjectNonExistent)?;
+		cursor.borrow_mut().set_cursor(name)?;
+		self.apply_cursor()
+	}
+
+	// binds wp_cursor_shape_manager_v1 if the compositor has it, returns whether it did
+	pub fn enable_cursor_shape(&mut self, registry: &mut Registry) -> Result<bool, Box<dyn Error>> {
+		if self.shape_device.is_some() {
+			return Ok(true);
+		}
+		if registry.does_implement(WaylandObjectKind::CursorShapeManager.as_str()).is_none() {
+			return Ok(false);
+		}
+		let manager = CursorShapeManager::new_bound(registry)?;
+		self.shape_device = Some(manager.borrow().get_pointer(self)?);
+		self.shape_manager = Some(manager);
+		Ok(true)
+	}
+
+	// uses the compositor's cursors if enable_cursor_shape succeeded,
+	// otherwise loads the matching xcursor into the cursor surface
+	pub fn set_cursor_shape(&mut self, shape: CursorShape) -> Result<(), Box<dyn Error>> {
+		self.shape = Some(shape);
+		if self.shape_device.is_none() {
+			let cursor = self.cursor.clone().ok_or(WaylandError::ObjectNonExistent)?;
+			cursor.borrow_mut().set_cursor_from(shape.xcursor_names())?;
+		}
+		self.apply_cursor()
+	}
+
+	// advances animated cursors, call this in the event loop
+	pub fn update_cursor(&mut self) -> Result<(), Box<dyn Error>> {
+		let Some(cursor) = &self.cursor else {
+			return Ok(());
+		};
+		let hotspot = cursor.borrow().hotspot();
+		cursor.borrow_mut().update()?;
+		// set_cursor again with the same surface is how the hotspot moves
+		if self.shape_device.is_none() && cursor.borrow().hotspot() != hotspot {
+			self.apply_cursor()?;
+		}
+		Ok(())
+	}
+
+	fn cursor_request(&self, serial: u32) -> Result<Option<WireRequest>, Box<dyn Error>> {
+		if let (Some(device), Some(shape)) = (&self.shape_device, self.shape) {
+			return Ok(Some(device.borrow().wl_set_shape(serial, shape)?));
+		}
+		match &self.cursor {
+			Some(cursor) => {
+				let cursor = cursor.borrow();
+				Ok(Some(self.wl_set_cursor(serial, cursor.surface_id(), cursor.hotspot())?))
+			}
+			None => Ok(None),
+		}
+	}
+
+	fn apply_cursor(&self) -> Result<(), Box<dyn Error>> {
+		if self.focus.is_none() {
+			return Ok(());
+		}
+		let serial = self.enter_serial.ok_or(WaylandError::NoSerial)?;
+		if let Some(mut req) = self.cursor_request(serial)? {
+			self.ctx.borrow().wlmm.send_request(&mut req)?;
+		}
+		Ok(())
+	}
+}
+
+impl WaylandObject for Pointer {
+	fn handle(
+		&mut self,
+		opcode: super::OpCode,
+		payload: &[u8],
+	) -> Result<Vec<EventAction>, Box<dyn Error>> {
+		let p = payload;
+		let mut pending = vec![];
+		match opcode {
+			// enter
+			0 => {
+				let serial = u32::from_wire(p)?;
+				let surface = u32::from_wire(&p[4..])?;
+				let x = Fixed::from_wire(&p[8..])?;
+				let y = Fixed::from_wire(&p[12..])?;
+				self.enter_serial = Some(serial);
+				self.focus = Some(surface);
+				self.position = (x.to_f64(), y.to_f64());
+				if let Some(req) = self.cursor_request(serial)? {
+					pending.push(EventAction::Request(req));
+				}
+				pending.push(EventAction::DebugMessage(
+					DebugLevel::Verbose,
+					format!("{} entered surface {}", self.as_str(), surface),
+				));
+			}
+			// leave
+			1 => {
+				self.focus = None;
+				self.pressed.clear();
+			}
+			// motion
+			2 => {
+				let x = Fixed::from_wire(&p[4..])?;
+				let y = Fixed::from_wire(&p[8..])?;
+				self.position = (x.to_f64(), y.to_f64());
+			}
+			// button
+			3 => {
+				let button = u32::from_wire(&p[8..])?;
+				let state = u32::from_wire(&p[12..])?;
+				if state == 1 {
+					self.button_serial = Some(u32::from_wire(p)?);
+					self.pressed.push(button);
+				} else {
+					self.pressed.retain(|b| *b != button);
+				}
+			}
+			// axis, frame, axis_source, axis_stop, axis_discrete, axis_value120,
+			// axis_relative_direction
+			4..=10 => {}
+			inv => return Err(WaylandError::InvalidOpCode(inv, self.as_str()).boxed()),
+		}
+		Ok(pending)
+	}
+
+	fn as_str(&self) -> &'static str {
+		WaylandObjectKind::Pointer.as_str()
+	}
+}
@@ -0,0 +1,225 @@
+use std::{cell::RefCell, error::Error, rc::Rc, time::Duration};
+
+use libc::{clock_gettime, clockid_t, timespec};
+
+use crate::wayland::{
+	CtxType, DebugLevel, EventAction, RcCell, WaylandError, WaylandObject, WaylandObjectKind,
+	registry::Registry,
+	surface::Surface,
+	wire::{FromWirePayload, Id, WireArgument, WireRequest},
+};
+
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresentationFlag {
+	// shown in step with the display refresh, no tearing
+	Vsync = 1,
+	// the timestamp comes from the display hardware
+	HwClock = 2,
+	// the hardware signalled the presentation itself
+	HwCompletion = 4,
+	// the buffer was scanned out directly, without a copy
+	ZeroCopy = 8,
+}
+
+pub struct Presentation {
+	pub id: Id,
+	ctx: CtxType,
+	// posix clock id all presentation timestamps are on, sent right after binding
+	pub clock_id: Option<u32>,
+}
+
+impl Presentation {
+	pub fn new_bound(registry: &mut Registry) -> Result<RcCell<Self>, Box<dyn Error>> {
+		let obj = Rc::new(RefCell::new(Self {
+			id: 0,
+			ctx: registry.ctx.clone(),
+			clock_id: None,
+		}));
+		let id = registry
+			.ctx
+			.borrow_mut()
+			.wlim
+			.new_id_registered(WaylandObjectKind::Presentation, obj.clone());
+		obj.borrow_mut().id = id;
+		registry.bind(id, WaylandObjectKind::Presentation, 1)?;
+		Ok(obj)
+	}
+
+	pub(crate) fn wl_destroy(&self) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 0,
+			args: vec![],
+		})
+	}
+
+	pub fn destroy(&self) -> Result<(), Box<dyn Error>> {
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_destroy()?)?;
+		self.ctx.borrow_mut().wlim.free_id(self.id)?;
+		Ok(())
+	}
+
+	pub(crate) fn wl_feedback(
+		&self,
+		surface_id: Id,
+		id: Id,
+	) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 1,
+			args: vec![WireArgument::Obj(surface_id), WireArgument::NewId(id)],
+		})
+	}
+
+	// for the content of the surface's next commit, so ask before committing
+	pub fn feedback(
+		&self,
+		surface: &Surface,
+	) -> Result<RcCell<PresentationFeedback>, Box<dyn Error>> {
+		let obj = Rc::new(RefCell::new(PresentationFeedback {
+			id: 0,
+			surface_id: surface.id,
+			sync_output: None,
+			presented: None,
+			discarded: false,
+		}));
+		let id = self
+			.ctx
+			.borrow_mut()
+			.wlim
+			.new_id_registered(WaylandObjectKind::PresentationFeedback, obj.clone());
+		obj.borrow_mut().id = id;
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_feedback(surface.id, id)?)?;
+		Ok(obj)
+	}
+
+	// the current time on the presentation clock
+	pub fn now(&self) -> Result<Duration, Box<dyn Error>> {
+		let clock_id = self.clock_id.ok_or(WaylandError::NoPresentationClock)?;
+		let mut ts = timespec {
+			tv_sec: 0,
+			tv_nsec: 0,
+		};
+		if unsafe { clock_gettime(clock_id as clockid_t, &mut ts) } != 0 {
+			return Err(Box::new(std::io::Error::last_os_error()));
+		}
+		Ok(Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
+	}
+
+	// how long ago the frame hit the screen
+	pub fn elapsed_since(&self, presented: &Presented) -> Result<Duration, Box<dyn Error>> {
+		Ok(self.now()?.saturating_sub(presented.time))
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Presented {
+	// since the epoch of the presentation clock
+	pub time: Duration,
+	// None when the output has no fixed refresh rate
+	pub refresh: Option<Duration>,
+	// vblank counter of the output, 0 if it has none
+	pub seq: u64,
+	pub flags: u32,
+}
+
+impl Presented {
+	pub fn has_flag(&self, flag: PresentationFlag) -> bool {
+		self.flags & flag as u32 != 0
+	}
+
+	// when the next refresh is predicted to happen, a good target for the next frame
+	pub fn next_refresh(&self) -> Option<Duration> {
+		Some(self.time + self.refresh?)
+	}
+}
+
+// single use, the compositor destroys it after presented or discarded
+pub struct PresentationFeedback {
+	pub id: Id,
+	pub surface_id: Id,
+	// wl_output the content was synchronized to
+	pub sync_output: Option<Id>,
+	pub presented: Option<Presented>,
+	// the content never made it to the screen
+	pub discarded: bool,
+}
+
+impl PresentationFeedback {
+	pub fn is_done(&self) -> bool {
+		self.presented.is_some() || self.discarded
+	}
+}
+
+impl WaylandObject for Presentation {
+	fn handle(
+		&mut self,
+		opcode: super::OpCode,
+		payload: &[u8],
+	) -> Result<Vec<EventAction>, Box<dyn Error>> {
+		let mut pending = vec![];
+		match opcode {
+			// clock_id
+			0 => {
+				let clock_id = u32::from_wire(payload)?;
+				self.clock_id = Some(clock_id);
+				pending.push(EventAction::DebugMessage(
+					DebugLevel::Verbose,
+					format!("{} clock id {}", self.as_str(), clock_id),
+				));
+			}
+			inv => return Err(WaylandError::InvalidOpCode(inv, self.as_str()).boxed()),
+		}
+		Ok(pending)
+	}
+
+	fn as_str(&self) -> &'static str {
+		WaylandObjectKind::Presentation.as_str()
+	}
+}
+
+impl WaylandObject for PresentationFeedback {
+	fn handle(
+		&mut self,
+		opcode: super::OpCode,
+		payload: &[u8],
+	) -> Result<Vec<EventAction>, Box<dyn Error>> {
+		let mut pending = vec![];
+		match opcode {
+			// sync_output
+			0 => {
+				self.sync_output = Some(u32::from_wire(payload)?);
+			}
+			// presented
+			1 => {
+				let mut args = [0u32; 7];
+				for (ix, arg) in args.iter_mut().enumerate() {
+					*arg = u32::from_wire(&payload[ix * 4..])?;
+				}
+				let [sec_hi, sec_lo, nsec, refresh, seq_hi, seq_lo, flags] = args;
+				let presented = Presented {
+					time: Duration::new((sec_hi as u64) << 32 | sec_lo as u64, nsec),
+					refresh: (refresh != 0).then(|| Duration::from_nanos(refresh as u64)),
+					seq: (seq_hi as u64) << 32 | seq_lo as u64,
+					flags,
+				};
+				pending.push(EventAction::DebugMessage(
+					DebugLevel::Verbose,
+					format!("{} {} presented // {:?}", self.as_str(), self.id, presented),
+				));
+				self.presented = Some(presented);
+			}
+			// discarded
+			2 => {
+				self.discarded = true;
+			}
+			inv => return Err(WaylandError::InvalidOpCode(inv, self.as_str()).boxed()),
+		}
+		Ok(pending)
+	}
+
+	fn as_str(&self) -> &'static str {
+		WaylandObjectKind::PresentationFeedback.as_str()
+	}
+}
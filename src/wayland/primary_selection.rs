@@ -0,0 +1,400 @@
+use std::{cell::RefCell, error::Error, fs::File, os::fd::AsRawFd, rc::Rc};
+
+use crate::wayland::{
+	CtxType, DebugLevel, EventAction, RcCell, WaylandError, WaylandObject, WaylandObjectKind,
+	data_device::{
+		Retired, SelectionOffer, SelectionSource, SourceData, TEXT_MIME_TYPES, make_pipe,
+		take_offer,
+	},
+	registry::Registry,
+	seat::Seat,
+	wire::{FromWirePayload, Id, WireArgument, WireRequest},
+};
+
+// what gets selected with the pointer, pasted with a middle click
+pub struct PrimarySelectionDeviceManager {
+	pub id: Id,
+	ctx: CtxType,
+}
+
+impl PrimarySelectionDeviceManager {
+	pub fn new_bound(registry: &mut Registry) -> Result<RcCell<Self>, Box<dyn Error>> {
+		let obj = Rc::new(RefCell::new(Self {
+			id: 0,
+			ctx: registry.ctx.clone(),
+		}));
+		let id = registry
+			.ctx
+			.borrow_mut()
+			.wlim
+			.new_id_registered(WaylandObjectKind::PrimarySelectionDeviceManager, obj.clone());
+		obj.borrow_mut().id = id;
+		registry.bind(id, WaylandObjectKind::PrimarySelectionDeviceManager, 1)?;
+		Ok(obj)
+	}
+
+	pub(crate) fn wl_create_source(&self, id: Id) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 0,
+			args: vec![WireArgument::NewId(id)],
+		})
+	}
+
+	pub fn create_source(
+		&self,
+		mime_types: &[&str],
+		data: Vec<u8>,
+	) -> Result<RcCell<PrimarySelectionSource>, Box<dyn Error>> {
+		let fds = self.ctx.borrow().wlmm.fds.clone();
+		let source = Rc::new(RefCell::new(PrimarySelectionSource {
+			id: 0,
+			ctx: self.ctx.clone(),
+			mime_types: vec![],
+			contents: SourceData::new(fds, data),
+			cancelled: false,
+		}));
+		let id = self
+			.ctx
+			.borrow_mut()
+			.wlim
+			.new_id_registered(WaylandObjectKind::PrimarySelectionSource, source.clone());
+		source.borrow_mut().id = id;
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_create_source(id)?)?;
+		for mime in mime_types {
+			source.borrow_mut().offer(mime)?;
+		}
+		Ok(source)
+	}
+
+	pub(crate) fn wl_get_device(&self, id: Id, seat_id: Id) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 1,
+			args: vec![WireArgument::NewId(id), WireArgument::Obj(seat_id)],
+		})
+	}
+
+	pub(crate) fn wl_destroy(&self) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 2,
+			args: vec![],
+		})
+	}
+
+	pub fn destroy(&self) -> Result<(), Box<dyn Error>> {
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_destroy()?)?;
+		self.ctx.borrow_mut().wlim.free_id(self.id)?;
+		Ok(())
+	}
+}
+
+pub struct PrimarySelectionDevice {
+	pub id: Id,
+	ctx: CtxType,
+	manager: RcCell<PrimarySelectionDeviceManager>,
+	// announced by data_offer, waiting for the selection they belong to
+	offers: Vec<RcCell<PrimarySelectionOffer>>,
+	pub selection: Option<RcCell<PrimarySelectionOffer>>,
+	// our own selection while we own it
+	source: Option<RcCell<PrimarySelectionSource>>,
+	retired: Retired,
+}
+
+impl PrimarySelectionDevice {
+	pub fn new_from_manager(
+		manager: &RcCell<PrimarySelectionDeviceManager>,
+		seat: &Seat,
+	) -> Result<RcCell<Self>, Box<dyn Error>> {
+		let ctx = manager.borrow().ctx.clone();
+		let device = Rc::new(RefCell::new(Self {
+			id: 0,
+			ctx: ctx.clone(),
+			manager: manager.clone(),
+			offers: vec![],
+			selection: None,
+			source: None,
+			retired: Retired::default(),
+		}));
+		let id = ctx
+			.borrow_mut()
+			.wlim
+			.new_id_registered(WaylandObjectKind::PrimarySelectionDevice, device.clone());
+		device.borrow_mut().id = id;
+		ctx.borrow().wlmm.send_request(&mut manager.borrow().wl_get_device(id, seat.id)?)?;
+		Ok(device)
+	}
+
+	pub(crate) fn wl_set_selection(
+		&self,
+		source_id: Id,
+		serial: u32,
+	) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 0,
+			args: vec![WireArgument::Obj(source_id), WireArgument::UnInt(serial)],
+		})
+	}
+
+	// serial of the input event that caused it, None clears the selection
+	pub fn set_selection(
+		&mut self,
+		source: Option<RcCell<PrimarySelectionSource>>,
+		serial: u32,
+	) -> Result<(), Box<dyn Error>> {
+		let id = source.as_ref().map_or(0, |s| s.borrow().id);
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_set_selection(id, serial)?)?;
+		let old = std::mem::replace(&mut self.source, source);
+		self.retired.retire(old.map(|old| old as _));
+		Ok(())
+	}
+
+	// same as DataDevice::flush_transfers, which calls this too
+	pub fn flush_transfers(&mut self) -> Result<(), Box<dyn Error>> {
+		let retired = self.retired.flush();
+		if let Some(source) = &self.source {
+			source.borrow_mut().contents.flush()?;
+		}
+		retired
+	}
+
+	pub fn set_text(&mut self, serial: u32, text: &str) -> Result<(), Box<dyn Error>> {
+		let source =
+			self.manager.borrow().create_source(&TEXT_MIME_TYPES, text.as_bytes().to_vec())?;
+		self.set_selection(Some(source), serial)
+	}
+
+	// blocks until the owner has written all of it, None if nothing is selected
+	pub fn get_text(&self) -> Result<Option<String>, Box<dyn Error>> {
+		// same as the clipboard, our own selection can't go through the compositor
+		if let Some(source) = &self.source
+			&& !source.borrow().cancelled
+		{
+			return Ok(Some(source.borrow().contents.text()));
+		}
+		self.selection.as_ref().map_or(Ok(None), |offer| offer.borrow().read_text())
+	}
+
+	pub(crate) fn wl_destroy(&self) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 1,
+			args: vec![],
+		})
+	}
+
+	pub fn destroy(&self) -> Result<(), Box<dyn Error>> {
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_destroy()?)?;
+		self.ctx.borrow_mut().wlim.free_id(self.id)?;
+		Ok(())
+	}
+}
+
+pub struct PrimarySelectionOffer {
+	pub id: Id,
+	ctx: CtxType,
+	pub mime_types: Vec<String>,
+}
+
+impl PrimarySelectionOffer {
+	pub(crate) fn wl_receive(&self, mime: &str, fd: i32) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 0,
+			args: vec![WireArgument::String(mime.to_string()), WireArgument::FileDescriptor(fd)],
+		})
+	}
+
+	pub fn receive(&self, mime: &str) -> Result<File, Box<dyn Error>> {
+		let (read, write) = make_pipe()?;
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_receive(mime, write.as_raw_fd())?)?;
+		Ok(read)
+	}
+
+	pub(crate) fn wl_destroy(&self) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 1,
+			args: vec![],
+		})
+	}
+
+	pub fn destroy(&self) -> Result<(), Box<dyn Error>> {
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_destroy()?)?;
+		self.ctx.borrow_mut().wlim.free_id(self.id)?;
+		Ok(())
+	}
+}
+
+impl SelectionOffer for PrimarySelectionOffer {
+	fn offer_id(&self) -> Id {
+		self.id
+	}
+
+	fn offered(&self) -> &[String] {
+		&self.mime_types
+	}
+
+	fn open(&self, mime: &str) -> Result<File, Box<dyn Error>> {
+		self.receive(mime)
+	}
+
+	fn destroy_request(&self) -> Result<WireRequest, Box<dyn Error>> {
+		self.wl_destroy()
+	}
+}
+
+pub struct PrimarySelectionSource {
+	pub id: Id,
+	ctx: CtxType,
+	pub mime_types: Vec<String>,
+	contents: SourceData,
+	// replaced by another selection, it's destroyed by then
+	pub cancelled: bool,
+}
+
+impl PrimarySelectionSource {
+	pub(crate) fn wl_offer(&self, mime: &str) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 0,
+			args: vec![WireArgument::String(mime.to_string())],
+		})
+	}
+
+	pub fn offer(&mut self, mime: &str) -> Result<(), Box<dyn Error>> {
+		self.mime_types.push(mime.to_string());
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_offer(mime)?)
+	}
+
+	pub(crate) fn wl_destroy(&self) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 1,
+			args: vec![],
+		})
+	}
+
+	pub fn destroy(&self) -> Result<(), Box<dyn Error>> {
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_destroy()?)?;
+		self.ctx.borrow_mut().wlim.free_id(self.id)?;
+		Ok(())
+	}
+}
+
+impl SelectionSource for PrimarySelectionSource {
+	fn contents(&mut self) -> &mut SourceData {
+		&mut self.contents
+	}
+}
+
+impl WaylandObject for PrimarySelectionDeviceManager {
+	fn handle(
+		&mut self,
+		opcode: super::OpCode,
+		_payload: &[u8],
+	) -> Result<Vec<EventAction>, Box<dyn Error>> {
+		Err(WaylandError::InvalidOpCode(opcode, self.as_str()).boxed())
+	}
+
+	fn as_str(&self) -> &'static str {
+		WaylandObjectKind::PrimarySelectionDeviceManager.as_str()
+	}
+}
+
+impl WaylandObject for PrimarySelectionDevice {
+	fn handle(
+		&mut self,
+		opcode: super::OpCode,
+		payload: &[u8],
+	) -> Result<Vec<EventAction>, Box<dyn Error>> {
+		let mut pending = vec![];
+		match opcode {
+			// data_offer
+			0 => {
+				let id = u32::from_wire(payload)?;
+				let offer = Rc::new(RefCell::new(PrimarySelectionOffer {
+					id,
+					ctx: self.ctx.clone(),
+					mime_types: vec![],
+				}));
+				self.offers.push(offer.clone());
+				pending.push(EventAction::NewObject(
+					id,
+					WaylandObjectKind::PrimarySelectionOffer,
+					offer,
+				));
+			}
+			// selection
+			1 => {
+				let id = u32::from_wire(payload)?;
+				if let Some(old) = self.selection.take() {
+					old.borrow().discard(&mut pending)?;
+				}
+				self.selection = take_offer(&mut self.offers, id);
+				pending.push(EventAction::DebugMessage(
+					DebugLevel::Verbose,
+					format!("{} selection is now offer {}", self.as_str(), id),
+				));
+			}
+			inv => return Err(WaylandError::InvalidOpCode(inv, self.as_str()).boxed()),
+		}
+		Ok(pending)
+	}
+
+	fn as_str(&self) -> &'static str {
+		WaylandObjectKind::PrimarySelectionDevice.as_str()
+	}
+}
+
+impl WaylandObject for PrimarySelectionOffer {
+	fn handle(
+		&mut self,
+		opcode: super::OpCode,
+		payload: &[u8],
+	) -> Result<Vec<EventAction>, Box<dyn Error>> {
+		match opcode {
+			// offer
+			0 => {
+				self.mime_types.push(String::from_wire(payload)?);
+			}
+			inv => return Err(WaylandError::InvalidOpCode(inv, self.as_str()).boxed()),
+		}
+		Ok(vec![])
+	}
+
+	fn as_str(&self) -> &'static str {
+		WaylandObjectKind::PrimarySelectionOffer.as_str()
+	}
+}
+
+impl WaylandObject for PrimarySelectionSource {
+	fn handle(
+		&mut self,
+		opcode: super::OpCode,
+		payload: &[u8],
+	) -> Result<Vec<EventAction>, Box<dyn Error>> {
+		let mut pending = vec![];
+		match opcode {
+			// send
+			0 => {
+				let who = format!("{} {}", self.as_str(), self.id);
+				self.contents.send(payload, who, &mut pending)?;
+			}
+			// cancelled
+			1 => {
+				self.cancelled = true;
+				pending.push(EventAction::Request(self.wl_destroy()?));
+				pending.push(EventAction::IdDeletion(self.id));
+			}
+			inv => return Err(WaylandError::InvalidOpCode(inv, self.as_str()).boxed()),
+		}
+		Ok(pending)
+	}
+
+	fn as_str(&self) -> &'static str {
+		WaylandObjectKind::PrimarySelectionSource.as_str()
+	}
+}
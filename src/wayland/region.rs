@@ -1,3 +1,11 @@
+use std::error::Error;
+
+use crate::wayland::{
+	CtxType, EventAction, OpCode, WaylandError, WaylandObject, WaylandObjectKind,
+	wire::{Id, WireArgument, WireRequest},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Region {
 	pub(crate) x: i32,
 	pub(crate) y: i32,
@@ -14,4 +22,100 @@ impl Region {
 			h,
 		}
 	}
+
+	pub fn is_empty(&self) -> bool {
+		self.w <= 0 || self.h <= 0
+	}
+
+	pub fn intersect(&self, other: Region) -> Option<Region> {
+		let x = self.x.max(other.x);
+		let y = self.y.max(other.y);
+		let w = (self.x + self.w).min(other.x + other.w) - x;
+		let h = (self.y + self.h).min(other.y + other.h) - y;
+		let region = Region::new(x, y, w, h);
+		(!region.is_empty()).then_some(region)
+	}
+
+	// smallest rect covering both
+	pub fn union(&self, other: Region) -> Region {
+		let x = self.x.min(other.x);
+		let y = self.y.min(other.y);
+		let w = (self.x + self.w).max(other.x + other.w) - x;
+		let h = (self.y + self.h).max(other.y + other.h) - y;
+		Region::new(x, y, w, h)
+	}
+}
+
+// wl_region, a set of rectangles the compositor keeps, built from adds and subtracts.
+// surfaces copy it on set_*_region, so it can be destroyed right after
+pub struct WlRegion {
+	pub id: Id,
+	pub(crate) ctx: CtxType,
+}
+
+impl WlRegion {
+	pub(crate) fn new(id: Id, ctx: CtxType) -> Self {
+		Self {
+			id,
+			ctx,
+		}
+	}
+
+	pub(crate) fn wl_destroy(&self) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 0,
+			args: vec![],
+		})
+	}
+
+	pub fn destroy(&self) -> Result<(), Box<dyn Error>> {
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_destroy()?)?;
+		self.ctx.borrow_mut().wlim.free_id(self.id)?;
+		Ok(())
+	}
+
+	fn wl_rect(&self, opcode: OpCode, region: Region) -> WireRequest {
+		WireRequest {
+			sender_id: self.id,
+			opcode,
+			args: vec![
+				WireArgument::Int(region.x),
+				WireArgument::Int(region.y),
+				WireArgument::Int(region.w),
+				WireArgument::Int(region.h),
+			],
+		}
+	}
+
+	pub(crate) fn wl_add(&self, region: Region) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(self.wl_rect(1, region))
+	}
+
+	// in surface coordinates
+	pub fn add(&self, region: Region) -> Result<(), Box<dyn Error>> {
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_add(region)?)
+	}
+
+	pub(crate) fn wl_subtract(&self, region: Region) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(self.wl_rect(2, region))
+	}
+
+	pub fn subtract(&self, region: Region) -> Result<(), Box<dyn Error>> {
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_subtract(region)?)
+	}
+}
+
+impl WaylandObject for WlRegion {
+	fn handle(
+		&mut self,
+		opcode: OpCode,
+		_payload: &[u8],
+	) -> Result<Vec<EventAction>, Box<dyn Error>> {
+		Err(WaylandError::InvalidOpCode(opcode, self.as_str()).boxed())
+	}
+
+	fn as_str(&self) -> &'static str {
+		WaylandObjectKind::Region.as_str()
+	}
 }
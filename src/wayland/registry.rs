@@ -26,6 +26,27 @@ impl Registry {
 		}
 	}
 
+	// binds a specific global, needed when an interface has more than one (wl_output)
+	pub(crate) fn wl_bind_name(
+		registry_id: Id,
+		global_id: u32,
+		id: Id,
+		object: WaylandObjectKind,
+		version: u32,
+	) -> WireRequest {
+		WireRequest {
+			// wl_registry id
+			sender_id: registry_id,
+			// first request in the proto
+			opcode: 0,
+			args: vec![
+				WireArgument::UnInt(global_id),
+				// WireArgument::NewId(new_id),
+				WireArgument::NewIdSpecific(object.as_str(), version, id),
+			],
+		}
+	}
+
 	fn wl_bind(
 		&mut self,
 		id: Id,
@@ -41,17 +62,10 @@ impl Registry {
 			.ok_or(WaylandError::NotInRegistry)?;
 		println!("bind global id for {}: {}", object.as_str(), global_id);
 
-		self.ctx.borrow().wlmm.send_request(&mut WireRequest {
-			// wl_registry id
-			sender_id: self.id,
-			// first request in the proto
-			opcode: 0,
-			args: vec![
-				WireArgument::UnInt(global_id),
-				// WireArgument::NewId(new_id),
-				WireArgument::NewIdSpecific(object.as_str(), version, id),
-			],
-		})?;
+		self.ctx
+			.borrow()
+			.wlmm
+			.send_request(&mut Self::wl_bind_name(self.id, global_id, id, object, version))?;
 		Ok(())
 	}
 
@@ -84,6 +98,12 @@ impl WaylandObject for Registry {
 				let interface = String::from_wire(&p[4..])?;
 				let version = u32::from_wire(&p[p.len() - 4..])?;
 				let msg = format!("inserted interface {} version {}", interface, version);
+				// outputs come and go at runtime so the context binds them itself
+				if interface == WaylandObjectKind::Output.as_str() {
+					pending.push(EventAction::BindOutput(self.id, name, version));
+				} else if interface == WaylandObjectKind::XdgOutputManager.as_str() {
+					pending.push(EventAction::BindXdgOutputManager(self.id, name, version));
+				}
 				self.inner.insert(
 					name,
 					RegistryEntry {
@@ -93,10 +113,16 @@ impl WaylandObject for Registry {
 				);
 				pending.push(EventAction::DebugMessage(DebugLevel::Verbose, msg));
 			}
-			// can global_remove even happen
+			// global_remove
 			1 => {
-				// let name = decode_event_payload(&p[8..], WireArgumentKind::UnInt)?;
-				todo!()
+				let name = u32::from_wire(p)?;
+				if let Some(entry) = self.inner.remove(&name) {
+					pending.push(EventAction::DebugMessage(
+						DebugLevel::Verbose,
+						format!("removed interface {} version {}", entry.interface, entry.version),
+					));
+				}
+				pending.push(EventAction::GlobalRemoved(name));
 			}
 			inv => {
 				return Err(WaylandError::InvalidOpCode(inv, self.as_str()).boxed());
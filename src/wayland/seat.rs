@@ -0,0 +1,137 @@
+use std::{cell::RefCell, error::Error, rc::Rc};
+
+use crate::wayland::{
+	CtxType, DebugLevel, EventAction, RcCell, WaylandError, WaylandObject, WaylandObjectKind,
+	keyboard::Keyboard,
+	pointer::Pointer,
+	registry::Registry,
+	wire::{FromWirePayload, Id, WireArgument, WireRequest},
+};
+
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeatCapability {
+	Pointer = 1,
+	Keyboard = 2,
+	Touch = 4,
+}
+
+pub struct Seat {
+	pub id: Id,
+	ctx: CtxType,
+	pub(crate) version: u32,
+	pub capabilities: u32,
+	pub name: Option<String>,
+}
+
+impl Seat {
+	pub fn new_bound(registry: &mut Registry) -> Result<RcCell<Self>, Box<dyn Error>> {
+		let version = registry
+			.does_implement(WaylandObjectKind::Seat.as_str())
+			.ok_or(WaylandError::NotInRegistry)?
+			.min(9);
+		let seat = Rc::new(RefCell::new(Self {
+			id: 0,
+			ctx: registry.ctx.clone(),
+			version,
+			capabilities: 0,
+			name: None,
+		}));
+		let id =
+			registry.ctx.borrow_mut().wlim.new_id_registered(WaylandObjectKind::Seat, seat.clone());
+		seat.borrow_mut().id = id;
+		registry.bind(id, WaylandObjectKind::Seat, version)?;
+		Ok(seat)
+	}
+
+	pub fn has_capability(&self, cap: SeatCapability) -> bool {
+		self.capabilities & cap as u32 != 0
+	}
+
+	pub(crate) fn wl_get_pointer(&self, id: Id) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 0,
+			args: vec![WireArgument::NewId(id)],
+		})
+	}
+
+	pub fn get_pointer(&self) -> Result<RcCell<Pointer>, Box<dyn Error>> {
+		let pointer = Rc::new(RefCell::new(Pointer::new(0, self.ctx.clone(), self.version)));
+		let id = self
+			.ctx
+			.borrow_mut()
+			.wlim
+			.new_id_registered(WaylandObjectKind::Pointer, pointer.clone());
+		pointer.borrow_mut().id = id;
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_get_pointer(id)?)?;
+		Ok(pointer)
+	}
+
+	pub(crate) fn wl_get_keyboard(&self, id: Id) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 1,
+			args: vec![WireArgument::NewId(id)],
+		})
+	}
+
+	pub fn get_keyboard(&self) -> Result<RcCell<Keyboard>, Box<dyn Error>> {
+		let keyboard = Rc::new(RefCell::new(Keyboard::new(0, self.ctx.clone(), self.version)));
+		let id = self
+			.ctx
+			.borrow_mut()
+			.wlim
+			.new_id_registered(WaylandObjectKind::Keyboard, keyboard.clone());
+		keyboard.borrow_mut().id = id;
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_get_keyboard(id)?)?;
+		Ok(keyboard)
+	}
+
+	pub(crate) fn wl_release(&self) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 3,
+			args: vec![],
+		})
+	}
+
+	pub fn release(&self) -> Result<(), Box<dyn Error>> {
+		// release only exists since v5
+		if self.version >= 5 {
+			self.ctx.borrow().wlmm.send_request(&mut self.wl_release()?)?;
+		}
+		self.ctx.borrow_mut().wlim.free_id(self.id)?;
+		Ok(())
+	}
+}
+
+impl WaylandObject for Seat {
+	fn handle(
+		&mut self,
+		opcode: super::OpCode,
+		payload: &[u8],
+	) -> Result<Vec<EventAction>, Box<dyn Error>> {
+		let mut pending = vec![];
+		match opcode {
+			// capabilities
+			0 => {
+				self.capabilities = u32::from_wire(payload)?;
+				pending.push(EventAction::DebugMessage(
+					DebugLevel::Verbose,
+					format!("{} capabilities: 0b{:03b}", self.as_str(), self.capabilities),
+				));
+			}
+			// name
+			1 => {
+				self.name = Some(String::from_wire(payload)?);
+			}
+			inv => return Err(WaylandError::InvalidOpCode(inv, self.as_str()).boxed()),
+		}
+		Ok(pending)
+	}
+
+	fn as_str(&self) -> &'static str {
+		WaylandObjectKind::Seat.as_str()
+	}
+}
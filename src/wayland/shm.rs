@@ -1,9 +1,11 @@
 use std::{
 	cell::RefCell,
-	collections::HashSet,
+	collections::{HashSet, hash_map::RandomState},
 	error::Error,
 	ffi::CString,
+	hash::{BuildHasher, Hasher},
 	os::{fd::RawFd, raw::c_void},
+	process,
 	ptr::{self, null_mut},
 	rc::Rc,
 };
@@ -12,45 +14,256 @@ use std::{
 use crate::{
 	drop,
 	wayland::{
-		CtxType, EventAction, RcCell, WaylandError, WaylandObject, WaylandObjectKind,
-		buffer::Buffer,
+		Context, CtxType, EventAction, RcCell, WaylandError, WaylandObject, WaylandObjectKind,
 		registry::Registry,
 		wire::{FromWirePayload, Id, WireArgument, WireRequest},
 	},
 };
 use libc::{
-	MAP_FAILED, MAP_SHARED, O_CREAT, O_RDWR, PROT_READ, PROT_WRITE, ftruncate, mmap, munmap,
-	shm_open, shm_unlink,
+	EEXIST, F_ADD_SEALS, F_SEAL_SHRINK, MAP_FAILED, MAP_SHARED, MFD_ALLOW_SEALING, MFD_CLOEXEC,
+	O_CLOEXEC, O_CREAT, O_EXCL, O_RDWR, PROT_READ, PROT_WRITE, close, fcntl, ftruncate,
+	memfd_create, mmap, munmap, shm_open, shm_unlink,
 };
 
-#[repr(C)]
-#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
-pub enum PixelFormat {
-	Argb888,
-	Xrgb888,
+const fn fourcc(code: &[u8; 4]) -> u32 {
+	(code[0] as u32) | (code[1] as u32) << 8 | (code[2] as u32) << 16 | (code[3] as u32) << 24
 }
 
-impl PixelFormat {
-	pub(crate) fn from_u32(processee: u32) -> Result<PixelFormat, Box<dyn Error>> {
-		match processee {
-			0 => Ok(PixelFormat::Argb888),
-			1 => Ok(PixelFormat::Xrgb888),
-			_ => Err(WaylandError::InvalidPixelFormat.boxed()),
+// name = wl_shm code, bits per pixel of the first plane, alpha, planes
+macro_rules! pixel_formats {
+	($($name:ident = $code:expr, $bpp:expr, $alpha:expr, $planes:expr;)*) => {
+		#[repr(u32)]
+		#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+		pub enum PixelFormat {
+			$($name = $code,)*
+		}
+
+		impl PixelFormat {
+			pub(crate) fn from_u32(processee: u32) -> Result<PixelFormat, Box<dyn Error>> {
+				match processee {
+					$(code if code == $code => Ok(PixelFormat::$name),)*
+					_ => Err(WaylandError::InvalidPixelFormat.boxed()),
+				}
+			}
+
+			pub fn bits_per_pixel(&self) -> u32 {
+				match self {
+					$(Self::$name => $bpp,)*
+				}
+			}
+
+			pub fn has_alpha(&self) -> bool {
+				match self {
+					$(Self::$name => $alpha,)*
+				}
+			}
+
+			pub fn planes(&self) -> usize {
+				match self {
+					$(Self::$name => $planes,)*
+				}
+			}
 		}
+	};
+}
+
+// argb8888 and xrgb8888 are the only ones not using their drm fourcc
+pixel_formats! {
+	Argb8888 = 0, 32, true, 1;
+	Xrgb8888 = 1, 32, false, 1;
+	C8 = fourcc(b"C8  "), 8, false, 1;
+	Rgb332 = fourcc(b"RGB8"), 8, false, 1;
+	Bgr233 = fourcc(b"BGR8"), 8, false, 1;
+	Xrgb4444 = fourcc(b"XR12"), 16, false, 1;
+	Xbgr4444 = fourcc(b"XB12"), 16, false, 1;
+	Rgbx4444 = fourcc(b"RX12"), 16, false, 1;
+	Bgrx4444 = fourcc(b"BX12"), 16, false, 1;
+	Argb4444 = fourcc(b"AR12"), 16, true, 1;
+	Abgr4444 = fourcc(b"AB12"), 16, true, 1;
+	Rgba4444 = fourcc(b"RA12"), 16, true, 1;
+	Bgra4444 = fourcc(b"BA12"), 16, true, 1;
+	Xrgb1555 = fourcc(b"XR15"), 16, false, 1;
+	Xbgr1555 = fourcc(b"XB15"), 16, false, 1;
+	Rgbx5551 = fourcc(b"RX15"), 16, false, 1;
+	Bgrx5551 = fourcc(b"BX15"), 16, false, 1;
+	Argb1555 = fourcc(b"AR15"), 16, true, 1;
+	Abgr1555 = fourcc(b"AB15"), 16, true, 1;
+	Rgba5551 = fourcc(b"RA15"), 16, true, 1;
+	Bgra5551 = fourcc(b"BA15"), 16, true, 1;
+	Rgb565 = fourcc(b"RG16"), 16, false, 1;
+	Bgr565 = fourcc(b"BG16"), 16, false, 1;
+	Rgb888 = fourcc(b"RG24"), 24, false, 1;
+	Bgr888 = fourcc(b"BG24"), 24, false, 1;
+	Xbgr8888 = fourcc(b"XB24"), 32, false, 1;
+	Rgbx8888 = fourcc(b"RX24"), 32, false, 1;
+	Bgrx8888 = fourcc(b"BX24"), 32, false, 1;
+	Abgr8888 = fourcc(b"AB24"), 32, true, 1;
+	Rgba8888 = fourcc(b"RA24"), 32, true, 1;
+	Bgra8888 = fourcc(b"BA24"), 32, true, 1;
+	Xrgb2101010 = fourcc(b"XR30"), 32, false, 1;
+	Xbgr2101010 = fourcc(b"XB30"), 32, false, 1;
+	Rgbx1010102 = fourcc(b"RX30"), 32, false, 1;
+	Bgrx1010102 = fourcc(b"BX30"), 32, false, 1;
+	Argb2101010 = fourcc(b"AR30"), 32, true, 1;
+	Abgr2101010 = fourcc(b"AB30"), 32, true, 1;
+	Rgba1010102 = fourcc(b"RA30"), 32, true, 1;
+	Bgra1010102 = fourcc(b"BA30"), 32, true, 1;
+	Yuyv = fourcc(b"YUYV"), 16, false, 1;
+	Yvyu = fourcc(b"YVYU"), 16, false, 1;
+	Uyvy = fourcc(b"UYVY"), 16, false, 1;
+	Vyuy = fourcc(b"VYUY"), 16, false, 1;
+	Ayuv = fourcc(b"AYUV"), 32, true, 1;
+	Nv12 = fourcc(b"NV12"), 8, false, 2;
+	Nv21 = fourcc(b"NV21"), 8, false, 2;
+	Nv16 = fourcc(b"NV16"), 8, false, 2;
+	Nv61 = fourcc(b"NV61"), 8, false, 2;
+	Yuv410 = fourcc(b"YUV9"), 8, false, 3;
+	Yvu410 = fourcc(b"YVU9"), 8, false, 3;
+	Yuv411 = fourcc(b"YU11"), 8, false, 3;
+	Yvu411 = fourcc(b"YV11"), 8, false, 3;
+	Yuv420 = fourcc(b"YU12"), 8, false, 3;
+	Yvu420 = fourcc(b"YV12"), 8, false, 3;
+	Yuv422 = fourcc(b"YU16"), 8, false, 3;
+	Yvu422 = fourcc(b"YV16"), 8, false, 3;
+	Yuv444 = fourcc(b"YU24"), 8, false, 3;
+	Yvu444 = fourcc(b"YV24"), 8, false, 3;
+	R8 = fourcc(b"R8  "), 8, false, 1;
+	R16 = fourcc(b"R16 "), 16, false, 1;
+	Rg88 = fourcc(b"RG88"), 16, false, 1;
+	Gr88 = fourcc(b"GR88"), 16, false, 1;
+	Rg1616 = fourcc(b"RG32"), 32, false, 1;
+	Gr1616 = fourcc(b"GR32"), 32, false, 1;
+	Xrgb16161616f = fourcc(b"XR4H"), 64, false, 1;
+	Xbgr16161616f = fourcc(b"XB4H"), 64, false, 1;
+	Argb16161616f = fourcc(b"AR4H"), 64, true, 1;
+	Abgr16161616f = fourcc(b"AB4H"), 64, true, 1;
+	Xyuv8888 = fourcc(b"XYUV"), 32, false, 1;
+	Vuy888 = fourcc(b"VU24"), 24, false, 1;
+	Vuy101010 = fourcc(b"VU30"), 30, false, 1;
+	Y210 = fourcc(b"Y210"), 32, false, 1;
+	Y212 = fourcc(b"Y212"), 32, false, 1;
+	Y216 = fourcc(b"Y216"), 32, false, 1;
+	Y410 = fourcc(b"Y410"), 32, true, 1;
+	Y412 = fourcc(b"Y412"), 64, true, 1;
+	Y416 = fourcc(b"Y416"), 64, true, 1;
+	Xvyu2101010 = fourcc(b"XV30"), 32, false, 1;
+	Xvyu12_16161616 = fourcc(b"XV36"), 64, false, 1;
+	Xvyu16161616 = fourcc(b"XV48"), 64, false, 1;
+	// 2x2 blocks packed into 64 bits
+	Y0l0 = fourcc(b"Y0L0"), 16, true, 1;
+	X0l0 = fourcc(b"X0L0"), 16, false, 1;
+	Y0l2 = fourcc(b"Y0L2"), 16, true, 1;
+	X0l2 = fourcc(b"X0L2"), 16, false, 1;
+	Yuv420_8bit = fourcc(b"YU08"), 12, false, 1;
+	Yuv420_10bit = fourcc(b"YU10"), 15, false, 1;
+	// color plane plus a separate a8 plane
+	Xrgb8888A8 = fourcc(b"XRA8"), 32, true, 2;
+	Xbgr8888A8 = fourcc(b"XBA8"), 32, true, 2;
+	Rgbx8888A8 = fourcc(b"RXA8"), 32, true, 2;
+	Bgrx8888A8 = fourcc(b"BXA8"), 32, true, 2;
+	Rgb888A8 = fourcc(b"R8A8"), 24, true, 2;
+	Bgr888A8 = fourcc(b"B8A8"), 24, true, 2;
+	Rgb565A8 = fourcc(b"R5A8"), 16, true, 2;
+	Bgr565A8 = fourcc(b"B5A8"), 16, true, 2;
+	Nv24 = fourcc(b"NV24"), 8, false, 2;
+	Nv42 = fourcc(b"NV42"), 8, false, 2;
+	P210 = fourcc(b"P210"), 16, false, 2;
+	P010 = fourcc(b"P010"), 16, false, 2;
+	P012 = fourcc(b"P012"), 16, false, 2;
+	P016 = fourcc(b"P016"), 16, false, 2;
+	Axbxgxrx106106106106 = fourcc(b"AB10"), 64, true, 1;
+	Nv15 = fourcc(b"NV15"), 10, false, 2;
+	Q410 = fourcc(b"Q410"), 16, false, 3;
+	Q401 = fourcc(b"Q401"), 16, false, 3;
+	Xrgb16161616 = fourcc(b"XR48"), 64, false, 1;
+	Xbgr16161616 = fourcc(b"XB48"), 64, false, 1;
+	Argb16161616 = fourcc(b"AR48"), 64, true, 1;
+	Abgr16161616 = fourcc(b"AB48"), 64, true, 1;
+	C1 = fourcc(b"C1  "), 1, false, 1;
+	C2 = fourcc(b"C2  "), 2, false, 1;
+	C4 = fourcc(b"C4  "), 4, false, 1;
+	D1 = fourcc(b"D1  "), 1, false, 1;
+	D2 = fourcc(b"D2  "), 2, false, 1;
+	D4 = fourcc(b"D4  "), 4, false, 1;
+	D8 = fourcc(b"D8  "), 8, false, 1;
+	R1 = fourcc(b"R1  "), 1, false, 1;
+	R2 = fourcc(b"R2  "), 2, false, 1;
+	R4 = fourcc(b"R4  "), 4, false, 1;
+	R10 = fourcc(b"R10 "), 16, false, 1;
+	R12 = fourcc(b"R12 "), 16, false, 1;
+	Avuy8888 = fourcc(b"AVUY"), 32, true, 1;
+	Xvuy8888 = fourcc(b"XVUY"), 32, false, 1;
+	// three 10 bit samples in every 32 bits, so a bit under 11
+	P030 = fourcc(b"P030"), 11, false, 2;
+}
+
+impl PixelFormat {
+	// every compositor has to support these two, even before wl_shm.format arrives
+	pub fn is_mandatory(&self) -> bool {
+		matches!(self, Self::Argb8888 | Self::Xrgb8888)
 	}
 
-	pub(crate) fn width(&self) -> usize {
-		match self {
-			Self::Argb888 => 4,
-			Self::Xrgb888 => 4,
+	// rounded up for formats packing several pixels into a byte
+	pub fn bytes_per_pixel(&self) -> usize {
+		self.bits_per_pixel().div_ceil(8) as usize
+	}
+}
+
+// memfd if the kernel has it, otherwise a throwaway posix shm object.
+// also says which one it was, only memfds can be sealed
+fn create_shm_fd() -> Result<(RawFd, bool), Box<dyn Error>> {
+	let fd = unsafe { memfd_create(c"wl-shm".as_ptr(), MFD_CLOEXEC | MFD_ALLOW_SEALING) };
+	if fd >= 0 {
+		return Ok((fd, true));
+	}
+	Ok((create_posix_shm_fd()?, false))
+}
+
+fn create_posix_shm_fd() -> Result<RawFd, Box<dyn Error>> {
+	// retry on the off chance the random name is taken
+	for _ in 0..100 {
+		let rand = RandomState::new().build_hasher().finish();
+		let name = CString::new(format!("/wl-shm-{}-{:016x}", process::id(), rand))?;
+		let fd = unsafe { shm_open(name.as_ptr(), O_RDWR | O_CREAT | O_EXCL | O_CLOEXEC, 0o600) };
+		if fd >= 0 {
+			// nobody needs the name after this, the fd keeps the object alive
+			if unsafe { shm_unlink(name.as_ptr()) } != 0 {
+				let err = std::io::Error::last_os_error();
+				unsafe { close(fd) };
+				return Err(Box::new(err));
+			}
+			return Ok(fd);
+		}
+		let err = std::io::Error::last_os_error();
+		if err.raw_os_error() != Some(EEXIST) {
+			return Err(Box::new(err));
 		}
 	}
+	Err(Box::new(std::io::Error::from_raw_os_error(EEXIST)))
+}
+
+fn create_sized_shm_fd(size: i32) -> Result<RawFd, Box<dyn Error>> {
+	let (fd, sealable) = create_shm_fd()?;
+	if unsafe { ftruncate(fd, size.into()) } != 0 {
+		let err = std::io::Error::last_os_error();
+		unsafe { close(fd) };
+		return Err(Box::new(err));
+	}
+	// wl_shm pools never shrink, so let the compositor rely on that.
+	// shm_open fds can't be sealed, which is fine
+	if sealable && unsafe { fcntl(fd, F_ADD_SEALS, F_SEAL_SHRINK) } != 0 {
+		let err = std::io::Error::last_os_error();
+		unsafe { close(fd) };
+		return Err(Box::new(err));
+	}
+	Ok(fd)
 }
 
 pub struct SharedMemory {
 	id: Id,
-	ctx: CtxType,
-	valid_pix_formats: HashSet<PixelFormat>,
+	pub(crate) ctx: CtxType,
+	// shared with the pools so buffers can be checked against it
+	valid_pix_formats: Rc<RefCell<HashSet<PixelFormat>>>,
 }
 
 impl SharedMemory {
@@ -58,12 +271,21 @@ impl SharedMemory {
 		Self {
 			id,
 			ctx,
-			valid_pix_formats: HashSet::new(),
+			valid_pix_formats: Rc::new(RefCell::new(HashSet::new())),
 		}
 	}
 
 	fn push_pix_format(&mut self, pf: PixelFormat) {
-		self.valid_pix_formats.insert(pf);
+		self.valid_pix_formats.borrow_mut().insert(pf);
+	}
+
+	// filled by wl_shm.format events, so empty until the next roundtrip after binding
+	pub fn valid_pix_formats(&self) -> HashSet<PixelFormat> {
+		self.valid_pix_formats.borrow().clone()
+	}
+
+	pub fn supports(&self, format: PixelFormat) -> bool {
+		format.is_mandatory() || self.valid_pix_formats.borrow().contains(&format)
 	}
 
 	pub fn new_bound_initialized(
@@ -79,14 +301,16 @@ impl SharedMemory {
 	}
 
 	pub fn make_pool(&mut self, size: i32) -> Result<RcCell<SharedMemoryPool>, Box<dyn Error>> {
-		// add method to get new names
-		let name = CString::new("wl-shm-1")?;
-		let fd = unsafe { shm_open(name.as_ptr(), O_RDWR | O_CREAT, 0) };
+		let fd = create_sized_shm_fd(size)?;
 		println!("fd: {}", fd);
-		unsafe { ftruncate(fd, size.into()) };
 
-		let shmpool =
-			Rc::new(RefCell::new(SharedMemoryPool::new(0, self.ctx.clone(), name, fd, size)));
+		let shmpool = Rc::new(RefCell::new(SharedMemoryPool::new(
+			0,
+			self.ctx.clone(),
+			fd,
+			size,
+			self.valid_pix_formats.clone(),
+		)));
 		let id = self
 			.ctx
 			.borrow_mut()
@@ -122,33 +346,51 @@ impl SharedMemory {
 pub struct SharedMemoryPool {
 	id: Id,
 	ctx: CtxType,
-	name: CString,
-	pub size: i32,
+	pub(crate) size: i32,
 	pub(crate) fd: RawFd,
-	pub slice: Option<*mut [u8]>,
+	// only handed out through Buffer::map_mut
+	pub(crate) slice: Option<*mut [u8]>,
 	ptr: Option<*mut c_void>,
+	valid_pix_formats: Rc<RefCell<HashSet<PixelFormat>>>,
+	// the fd is closed by then, its number may belong to something else
+	destroyed: bool,
 }
 
 impl SharedMemoryPool {
-	pub fn new(id: Id, ctx: CtxType, name: CString, size: i32, fd: RawFd) -> Self {
+	pub fn new(
+		id: Id,
+		ctx: CtxType,
+		fd: RawFd,
+		size: i32,
+		valid_pix_formats: Rc<RefCell<HashSet<PixelFormat>>>,
+	) -> Self {
 		Self {
 			id,
 			ctx,
-			name,
 			size,
 			fd,
 			slice: None,
 			ptr: None,
+			valid_pix_formats,
+			destroyed: false,
 		}
 	}
 
+	pub fn size(&self) -> i32 {
+		self.size
+	}
+
+	pub fn supports(&self, format: PixelFormat) -> bool {
+		format.is_mandatory() || self.valid_pix_formats.borrow().contains(&format)
+	}
+
 	pub(crate) fn wl_create_buffer(
 		&self,
 		id: Id,
 		(offset, width, height, stride): (i32, i32, i32, i32),
 		format: PixelFormat,
-	) -> Result<(), Box<dyn Error>> {
-		self.ctx.borrow().wlmm.send_request(&mut WireRequest {
+	) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
 			sender_id: self.id,
 			opcode: 0,
 			args: vec![
@@ -163,7 +405,10 @@ impl SharedMemoryPool {
 	}
 
 	pub(crate) fn unmap(&self) -> Result<(), std::io::Error> {
-		let r = unsafe { munmap(self.ptr.unwrap(), self.size as usize) };
+		let Some(ptr) = self.ptr else {
+			return Ok(());
+		};
+		let r = unsafe { munmap(ptr, self.size as usize) };
 		if r == 0 {
 			Ok(())
 		} else {
@@ -179,8 +424,8 @@ impl SharedMemoryPool {
 		})
 	}
 
-	fn unlink(&self) -> Result<(), std::io::Error> {
-		let r = unsafe { shm_unlink(self.name.as_ptr()) };
+	fn close(&self) -> Result<(), std::io::Error> {
+		let r = unsafe { close(self.fd) };
 		if r == 0 {
 			Ok(())
 		} else {
@@ -188,17 +433,25 @@ impl SharedMemoryPool {
 		}
 	}
 
-	pub fn destroy(&self) -> Result<(), Box<dyn Error>> {
+	// buffers of it can't be mapped afterwards. dropping it destroys it too, once is enough
+	pub fn destroy(&mut self) -> Result<(), Box<dyn Error>> {
+		if self.destroyed {
+			return Ok(());
+		}
+		self.destroyed = true;
 		self.wl_destroy()?;
 		self.ctx.borrow_mut().wlim.free_id(self.id)?;
-		self.unlink()?;
 		self.unmap()?;
+		self.ptr = None;
+		self.slice = None;
+		self.close()?;
 		Ok(())
 	}
 
 	pub(crate) fn update_ptr(&mut self) -> Result<(), Box<dyn Error>> {
-		let ptr =
-			unsafe { mmap(null_mut(), self.size as usize, PROT_READ | PROT_WRITE, MAP_SHARED, self.fd, 0) };
+		let ptr = unsafe {
+			mmap(null_mut(), self.size as usize, PROT_READ | PROT_WRITE, MAP_SHARED, self.fd, 0)
+		};
 		if ptr == MAP_FAILED {
 			return Err(Box::new(std::io::Error::last_os_error()));
 		} else {
@@ -209,21 +462,38 @@ impl SharedMemoryPool {
 		Ok(())
 	}
 
+	pub(crate) fn wl_resize(&self, size: i32) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 2,
+			args: vec![WireArgument::Int(size)],
+		})
+	}
+
+	// pools can only grow, asking for less keeps the current size
 	pub fn resize(&mut self, size: i32) -> Result<(), Box<dyn Error>> {
-		if let Some(old_ptr) = self.ptr {
-			let r = unsafe { munmap(old_ptr, self.size as usize) };
-			if r != 0 {
-				return Err(Box::new(std::io::Error::last_os_error()));
-			}
+		let ctx = self.ctx.clone();
+		self.resize_in(&ctx.borrow(), size)
+	}
+
+	// for when the context is already borrowed, like while handling events
+	pub(crate) fn resize_in(&mut self, ctx: &Context, size: i32) -> Result<(), Box<dyn Error>> {
+		if self.destroyed {
+			return Err(WaylandError::ObjectNonExistent.boxed());
+		}
+		if size <= self.size {
+			return Ok(());
 		}
-		self.size = size;
 		let r = unsafe { ftruncate(self.fd, size.into()) };
-		if r == 0 {
-			Ok(())
-		} else {
-			Err(std::io::Error::last_os_error())
-		}?;
-		self.update_ptr()
+		if r != 0 {
+			return Err(Box::new(std::io::Error::last_os_error()));
+		}
+		self.unmap()?;
+		self.ptr = None;
+		self.slice = None;
+		self.size = size;
+		self.update_ptr()?;
+		ctx.wlmm.send_request(&mut self.wl_resize(size)?)
 	}
 }
 
@@ -265,8 +535,8 @@ impl WaylandObject for SharedMemory {
 impl WaylandObject for SharedMemoryPool {
 	fn handle(
 		&mut self,
-		opcode: super::OpCode,
-		payload: &[u8],
+		_opcode: super::OpCode,
+		_payload: &[u8],
 	) -> Result<Vec<EventAction>, Box<dyn Error>> {
 		todo!()
 	}
@@ -277,3 +547,62 @@ impl WaylandObject for SharedMemoryPool {
 }
 
 drop!(SharedMemoryPool);
+
+#[cfg(test)]
+mod tests {
+	use std::{
+		fs::File,
+		io::{Read, Seek, SeekFrom, Write},
+		os::fd::FromRawFd,
+	};
+
+	use super::*;
+
+	const POOLS: usize = 64;
+	const SIZE: i32 = 4096;
+
+	fn check_distinct(fds: Vec<RawFd>) {
+		let mut files: Vec<File> =
+			fds.into_iter().map(|fd| unsafe { File::from_raw_fd(fd) }).collect();
+		for (ix, file) in files.iter_mut().enumerate() {
+			assert_eq!(file.metadata().unwrap().len(), SIZE as u64);
+			file.write_all(&[ix as u8; 16]).unwrap();
+		}
+		// a shared backing object would show the last write everywhere
+		for (ix, file) in files.iter_mut().enumerate() {
+			let mut data = [0; 16];
+			file.seek(SeekFrom::Start(0)).unwrap();
+			file.read_exact(&mut data).unwrap();
+			assert_eq!(data, [ix as u8; 16]);
+		}
+	}
+
+	#[test]
+	fn many_pools_at_once() {
+		let fds: Vec<RawFd> = (0..POOLS).map(|_| create_sized_shm_fd(SIZE).unwrap()).collect();
+		for fd in &fds {
+			// sealed against shrinking when it's a memfd
+			if unsafe { fcntl(*fd, libc::F_GET_SEALS) } >= 0 {
+				assert_ne!(unsafe { ftruncate(*fd, 0) }, 0);
+			}
+		}
+		check_distinct(fds);
+	}
+
+	#[test]
+	fn many_posix_shm_fallbacks_at_once() {
+		let fds: Vec<RawFd> = (0..POOLS)
+			.map(|_| {
+				let fd = create_posix_shm_fd().unwrap();
+				assert_eq!(unsafe { ftruncate(fd, SIZE.into()) }, 0);
+				fd
+			})
+			.collect();
+		check_distinct(fds);
+		// every name got unlinked right away
+		let prefix = format!("wl-shm-{}-", process::id());
+		if let Ok(dir) = std::fs::read_dir("/dev/shm") {
+			assert!(!dir.flatten().any(|e| e.file_name().to_string_lossy().starts_with(&prefix)));
+		}
+	}
+}
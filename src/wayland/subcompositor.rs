@@ -0,0 +1,269 @@
+use std::{
+	cell::RefCell,
+	error::Error,
+	rc::{Rc, Weak},
+};
+
+use crate::wayland::{
+	CtxType, EventAction, RcCell, WaylandError, WaylandObject, WaylandObjectKind,
+	registry::Registry,
+	surface::Surface,
+	wire::{Id, WireArgument, WireRequest},
+};
+
+pub struct Subcompositor {
+	pub id: Id,
+	ctx: CtxType,
+}
+
+impl Subcompositor {
+	pub fn new_bound(registry: &mut Registry) -> Result<RcCell<Self>, Box<dyn Error>> {
+		let obj = Rc::new(RefCell::new(Self {
+			id: 0,
+			ctx: registry.ctx.clone(),
+		}));
+		let id = registry
+			.ctx
+			.borrow_mut()
+			.wlim
+			.new_id_registered(WaylandObjectKind::Subcompositor, obj.clone());
+		obj.borrow_mut().id = id;
+		registry.bind(id, WaylandObjectKind::Subcompositor, 1)?;
+		Ok(obj)
+	}
+
+	pub(crate) fn wl_destroy(&self) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 0,
+			args: vec![],
+		})
+	}
+
+	pub fn destroy(&self) -> Result<(), Box<dyn Error>> {
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_destroy()?)?;
+		self.ctx.borrow_mut().wlim.free_id(self.id)?;
+		Ok(())
+	}
+
+	pub(crate) fn wl_get_subsurface(
+		&self,
+		id: Id,
+		surface_id: Id,
+		parent_id: Id,
+	) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 1,
+			args: vec![
+				WireArgument::NewId(id),
+				WireArgument::Obj(surface_id),
+				WireArgument::Obj(parent_id),
+			],
+		})
+	}
+
+	// the surface must not have a role yet. it starts out synchronized at (0, 0),
+	// and is only mapped once the parent is
+	pub fn get_subsurface(
+		&self,
+		surface: &RcCell<Surface>,
+		parent: &RcCell<Surface>,
+	) -> Result<RcCell<Subsurface>, Box<dyn Error>> {
+		let parent_sync =
+			parent.borrow().subsurface.upgrade().is_some_and(|sub| sub.borrow().is_synchronized());
+		let obj = Rc::new(RefCell::new(Subsurface {
+			id: 0,
+			ctx: self.ctx.clone(),
+			surface: surface.clone(),
+			parent: Rc::downgrade(parent),
+			position: (0, 0),
+			pending_position: None,
+			sync: true,
+			parent_sync,
+			cached: false,
+			destroyed: false,
+		}));
+		let id = self
+			.ctx
+			.borrow_mut()
+			.wlim
+			.new_id_registered(WaylandObjectKind::Subsurface, obj.clone());
+		obj.borrow_mut().id = id;
+		let (surface_id, parent_id) = (surface.borrow().id, parent.borrow().id);
+		self.ctx
+			.borrow()
+			.wlmm
+			.send_request(&mut self.wl_get_subsurface(id, surface_id, parent_id)?)?;
+		surface.borrow_mut().subsurface = Rc::downgrade(&obj);
+		parent.borrow_mut().children.push(obj.clone());
+		obj.borrow().propagate_sync();
+		Ok(obj)
+	}
+}
+
+pub struct Subsurface {
+	pub id: Id,
+	ctx: CtxType,
+	pub surface: RcCell<Surface>,
+	pub parent: Weak<RefCell<Surface>>,
+	// relative to the parent, as of the parent's last commit
+	pub position: (i32, i32),
+	pending_position: Option<(i32, i32)>,
+	pub sync: bool,
+	// whether an ancestor is synchronized. kept here, since children commit during the
+	// parent's redraw, while the parent surface is borrowed
+	parent_sync: bool,
+	// the surface committed while synchronized, it shows on the parent's next commit
+	pub cached: bool,
+	// still among the parent's children until the parent commits
+	pub destroyed: bool,
+}
+
+impl Subsurface {
+	pub(crate) fn wl_destroy(&self) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 0,
+			args: vec![],
+		})
+	}
+
+	// the surface gets unmapped right away and loses its role
+	pub fn destroy(&mut self) -> Result<(), Box<dyn Error>> {
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_destroy()?)?;
+		self.ctx.borrow_mut().wlim.free_id(self.id)?;
+		self.surface.borrow_mut().subsurface = Weak::new();
+		self.destroyed = true;
+		Ok(())
+	}
+
+	pub(crate) fn wl_set_position(
+		&self,
+		(x, y): (i32, i32),
+	) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 1,
+			args: vec![WireArgument::Int(x), WireArgument::Int(y)],
+		})
+	}
+
+	// in the parent's surface coordinates, applied on the parent's next commit
+	pub fn set_position(&mut self, pos: (i32, i32)) -> Result<(), Box<dyn Error>> {
+		self.pending_position = Some(pos);
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_set_position(pos)?)
+	}
+
+	pub(crate) fn wl_place_above(&self, sibling_id: Id) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 2,
+			args: vec![WireArgument::Obj(sibling_id)],
+		})
+	}
+
+	// sibling is the parent or another subsurface of it, applied on the parent's next commit
+	pub fn place_above(&self, sibling: &Surface) -> Result<(), Box<dyn Error>> {
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_place_above(sibling.id)?)
+	}
+
+	pub(crate) fn wl_place_below(&self, sibling_id: Id) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 3,
+			args: vec![WireArgument::Obj(sibling_id)],
+		})
+	}
+
+	pub fn place_below(&self, sibling: &Surface) -> Result<(), Box<dyn Error>> {
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_place_below(sibling.id)?)
+	}
+
+	pub(crate) fn wl_set_sync(&self) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 4,
+			args: vec![],
+		})
+	}
+
+	// commits of the surface get cached and applied together with the parent's
+	pub fn set_sync(&mut self) -> Result<(), Box<dyn Error>> {
+		self.sync = true;
+		self.propagate_sync();
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_set_sync()?)
+	}
+
+	pub(crate) fn wl_set_desync(&self) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 5,
+			args: vec![],
+		})
+	}
+
+	// commits of the surface apply on their own, unless the parent is synchronized itself.
+	// whatever is cached still waits for the parent, or goes along with the next commit
+	pub fn set_desync(&mut self) -> Result<(), Box<dyn Error>> {
+		self.sync = false;
+		self.propagate_sync();
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_set_desync()?)
+	}
+
+	// a subsurface with a synchronized ancestor acts synchronized, whatever its own mode
+	pub fn is_synchronized(&self) -> bool {
+		self.sync || self.parent_sync
+	}
+
+	// down to every descendant, after our own effective mode might have changed
+	fn propagate_sync(&self) {
+		let sync = self.is_synchronized();
+		for child in &self.surface.borrow().children {
+			let mut child = child.borrow_mut();
+			child.parent_sync = sync;
+			child.propagate_sync();
+		}
+	}
+
+	// the parent's state got applied, which takes the position and any cached state with it
+	pub(crate) fn parent_applied(&mut self) {
+		if let Some(pos) = self.pending_position.take() {
+			self.position = pos;
+		}
+		if self.cached {
+			self.cached = false;
+			for child in &self.surface.borrow().children {
+				child.borrow_mut().parent_applied();
+			}
+		}
+	}
+}
+
+impl WaylandObject for Subcompositor {
+	fn handle(
+		&mut self,
+		opcode: super::OpCode,
+		_payload: &[u8],
+	) -> Result<Vec<EventAction>, Box<dyn Error>> {
+		Err(WaylandError::InvalidOpCode(opcode, self.as_str()).boxed())
+	}
+
+	fn as_str(&self) -> &'static str {
+		WaylandObjectKind::Subcompositor.as_str()
+	}
+}
+
+impl WaylandObject for Subsurface {
+	fn handle(
+		&mut self,
+		opcode: super::OpCode,
+		_payload: &[u8],
+	) -> Result<Vec<EventAction>, Box<dyn Error>> {
+		Err(WaylandError::InvalidOpCode(opcode, self.as_str()).boxed())
+	}
+
+	fn as_str(&self) -> &'static str {
+		WaylandObjectKind::Subsurface.as_str()
+	}
+}
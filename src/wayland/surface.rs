@@ -1,13 +1,58 @@
-use std::error::Error;
+use std::{cell::RefCell, error::Error, rc::Weak};
 
 use crate::wayland::{
-	CtxType, EventAction, RcCell, WaylandError, WaylandObject, WaylandObjectKind, buffer::Buffer, callback::Callback, region::Region, wire::{Id, WireArgument, WireRequest}
+	Context, CtxType, DebugLevel, Event, EventAction, RcCell, WaylandError, WaylandObject,
+	WaylandObjectKind,
+	buffer::Buffer,
+	callback::Callback,
+	output::Transform,
+	region::{Region, WlRegion},
+	subcompositor::Subsurface,
+	swapchain::Swapchain,
+	viewporter::Viewport,
+	wire::{FromWirePayload, Id, WireArgument, WireRequest},
 };
 
+const MAX_DAMAGE_RECTS: usize = 16;
+
+// gets the surface and the frame time in ms, should attach, damage and commit
+pub type RedrawFn = dyn FnMut(&mut Surface, u32) -> Result<(), Box<dyn Error>>;
+
 pub struct Surface {
 	pub id: Id,
 	pub(crate) ctx: CtxType,
 	pub(crate) attached_buf: Option<RcCell<Buffer>>,
+	// attached since the last commit, the buffer is the compositor's after that
+	attach_pending: bool,
+	// wl_output ids, see Context::outputs_of
+	pub outputs: Vec<Id>,
+	// only sent by wl_surface v6, see Context::preferred_scale_of for a fallback
+	pub preferred_scale: Option<i32>,
+	pub preferred_transform: Option<Transform>,
+	pub buffer_scale: i32,
+	// the scale last sent, buffer_scale waits for a buffer of the matching size
+	sent_scale: i32,
+	pub buffer_transform: Transform,
+	// size in surface coordinates, the buffer gets scaled from this
+	pub logical_size: Option<(i32, i32)>,
+	// wp_fractional_scale_v1, in 120ths
+	pub fractional_scale: Option<u32>,
+	pub viewport: Option<RcCell<Viewport>>,
+	pub swapchain: Option<RcCell<Swapchain>>,
+	// buffer damage since the last commit, sent with it
+	pub damage: Vec<Region>,
+	// set while the surface has the subsurface role
+	pub(crate) subsurface: Weak<RefCell<Subsurface>>,
+	pub children: Vec<RcCell<Subsurface>>,
+	// frame scheduling, see update_frame
+	dirty: bool,
+	frame_cb: Option<RcCell<Callback>>,
+	// set by commit, tells update_frame whether the redraw committed
+	committed: bool,
+	last_frame_time: u32,
+	redraw: Option<Box<RedrawFn>>,
+	// xdg_toplevel suspended state, nothing gets drawn while it is set
+	pub suspended: bool,
 }
 
 impl Surface {
@@ -16,6 +61,26 @@ impl Surface {
 			id,
 			ctx,
 			attached_buf: None,
+			attach_pending: false,
+			outputs: vec![],
+			preferred_scale: None,
+			preferred_transform: None,
+			buffer_scale: 1,
+			sent_scale: 1,
+			buffer_transform: Transform::Normal,
+			logical_size: None,
+			fractional_scale: None,
+			viewport: None,
+			swapchain: None,
+			damage: vec![],
+			subsurface: Weak::new(),
+			children: vec![],
+			dirty: false,
+			frame_cb: None,
+			committed: false,
+			last_frame_time: 0,
+			redraw: None,
+			suspended: false,
 		}
 	}
 
@@ -47,8 +112,10 @@ impl Surface {
 	}
 
 	pub fn attach_buffer(&mut self) -> Result<(), Box<dyn Error>> {
-		let buf =  self.attached_buf.clone().ok_or(WaylandError::BufferObjectNotAttached)?;
-		self.ctx.borrow().wlmm.send_request(&mut self.wl_attach(buf.borrow().id)?)
+		let buf = self.attached_buf.clone().ok_or(WaylandError::BufferObjectNotAttached)?;
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_attach(buf.borrow().id)?)?;
+		self.attach_pending = true;
+		Ok(())
 	}
 
 	pub(crate) fn wl_commit(&self) -> Result<(), Box<dyn Error>> {
@@ -59,8 +126,75 @@ impl Surface {
 		})
 	}
 
-	pub fn commit(&self) -> Result<(), Box<dyn Error>> {
-		self.wl_commit()
+	// the buffer stays busy until its release event
+	pub fn commit(&mut self) -> Result<(), Box<dyn Error>> {
+		let damage = std::mem::take(&mut self.damage);
+		for region in &damage {
+			self.damage_buffer(*region)?;
+		}
+		if self.sent_scale != self.buffer_scale && self.buffer_fits_scale() {
+			let scale = self.buffer_scale;
+			self.ctx.borrow().wlmm.send_request(&mut self.wl_set_buffer_scale(scale)?)?;
+			self.sent_scale = scale;
+		}
+		self.wl_commit()?;
+		self.committed = true;
+		if self.attach_pending
+			&& let Some(buf) = &self.attached_buf
+		{
+			buf.borrow_mut().in_use = true;
+			if let Some(swapchain) = &self.swapchain {
+				swapchain.borrow_mut().presented(buf, &damage);
+			}
+		}
+		self.attach_pending = false;
+
+		// synchronized subsurfaces only show with the parent's next commit
+		let sub = self.subsurface.upgrade();
+		let sync = sub.as_ref().is_some_and(|sub| sub.borrow().is_synchronized());
+		if let Some(sub) = sub {
+			sub.borrow_mut().cached = sync;
+		}
+		self.children.retain(|child| !child.borrow().destroyed);
+		if !sync {
+			for child in &self.children {
+				child.borrow_mut().parent_applied();
+			}
+		}
+		Ok(())
+	}
+
+	pub(crate) fn wl_set_opaque_region(
+		&self,
+		region_id: Id,
+	) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 4,
+			args: vec![WireArgument::Obj(region_id)],
+		})
+	}
+
+	// a hint that lets the compositor skip what is under it, None means nothing is opaque.
+	// applied on the next commit
+	pub fn set_opaque_region(&self, region: Option<&WlRegion>) -> Result<(), Box<dyn Error>> {
+		let id = region.map_or(0, |r| r.id);
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_set_opaque_region(id)?)
+	}
+
+	pub(crate) fn wl_set_input_region(&self, region_id: Id) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 5,
+			args: vec![WireArgument::Obj(region_id)],
+		})
+	}
+
+	// pointer and touch outside of it go to whatever is below, None means the whole surface.
+	// applied on the next commit
+	pub fn set_input_region(&self, region: Option<&WlRegion>) -> Result<(), Box<dyn Error>> {
+		let id = region.map_or(0, |r| r.id);
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_set_input_region(id)?)
 	}
 
 	pub(crate) fn wl_damage_buffer(&self, region: Region) -> Result<WireRequest, Box<dyn Error>> {
@@ -80,16 +214,127 @@ impl Surface {
 		self.ctx.borrow().wlmm.send_request(&mut self.wl_damage_buffer(region)?)
 	}
 
-	pub fn repaint(&self) -> Result<(), Box<dyn Error>> {
+	// in buffer coordinates, overlapping rects get merged and the lot is sent on commit
+	pub fn add_damage(&mut self, region: Region) {
+		if region.is_empty() {
+			return;
+		}
+		let mut region = region;
+		while let Some(ix) = self.damage.iter().position(|d| d.intersect(region).is_some()) {
+			region = region.union(self.damage.swap_remove(ix));
+		}
+		self.damage.push(region);
+		// past a handful of rects one big one is cheaper for the compositor
+		if self.damage.len() > MAX_DAMAGE_RECTS {
+			let all = self.damage.iter().fold(region, |all, d| all.union(*d));
+			self.damage = vec![all];
+		}
+	}
+
+	pub fn repaint(&mut self) -> Result<(), Box<dyn Error>> {
 		if let Some(buf) = &self.attached_buf {
-			let buf = buf.borrow();
-			self.ctx.borrow().wlmm.send_request(&mut self.wl_damage_buffer(Region {
-				x: 0,
-				y: 0,
-				w: buf.width,
-				h: buf.height,
-			})?)?;
+			let (w, h) = {
+				let buf = buf.borrow();
+				(buf.width, buf.height)
+			};
+			self.add_damage(Region::new(0, 0, w, h));
+		};
+		Ok(())
+	}
+
+	pub(crate) fn wl_set_buffer_transform(
+		&self,
+		transform: Transform,
+	) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 7,
+			args: vec![WireArgument::Int(transform as i32)],
+		})
+	}
+
+	pub fn set_buffer_transform(&mut self, transform: Transform) -> Result<(), Box<dyn Error>> {
+		self.buffer_transform = transform;
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_set_buffer_transform(transform)?)?;
+		self.resize_buffer()
+	}
+
+	pub(crate) fn wl_set_buffer_scale(&self, scale: i32) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 8,
+			args: vec![WireArgument::Int(scale)],
+		})
+	}
+
+	// gets overridden again once the surface lands on an output with another scale.
+	// sent with the first commit whose buffer has the size for it
+	pub fn set_buffer_scale(&mut self, scale: i32) -> Result<(), Box<dyn Error>> {
+		self.buffer_scale = scale;
+		self.resize_buffer()
+	}
+
+	// an old buffer with the new scale is a protocol error unless its size divides
+	fn buffer_fits_scale(&self) -> bool {
+		let Some(buf) = &self.attached_buf else {
+			return false;
+		};
+		let buf = buf.borrow();
+		match self.buffer_size() {
+			Some(size) => (buf.width, buf.height) == size,
+			None => {
+				let scale = self.buffer_scale;
+				scale > 0 && buf.width % scale == 0 && buf.height % scale == 0
+			}
+		}
+	}
+
+	pub(crate) fn uses_fractional_scale(&self) -> bool {
+		self.fractional_scale.is_some() && self.viewport.is_some()
+	}
+
+	pub fn buffer_size(&self) -> Option<(i32, i32)> {
+		let (w, h) = self.logical_size?;
+		let (w, h) = match self.fractional_scale {
+			Some(scale) if self.uses_fractional_scale() => {
+				let scale = scale as i32;
+				((w * scale + 119) / 120, (h * scale + 119) / 120)
+			}
+			_ => (w * self.buffer_scale, h * self.buffer_scale),
+		};
+		match self.buffer_transform {
+			Transform::Rotated90
+			| Transform::Rotated270
+			| Transform::Flipped90
+			| Transform::Flipped270 => Some((h, w)),
+			_ => Some((w, h)),
+		}
+	}
+
+	pub(crate) fn resize_buffer(&mut self) -> Result<(), Box<dyn Error>> {
+		let ctx = self.ctx.clone();
+		self.resize_buffer_in(&mut ctx.borrow_mut())
+	}
+
+	pub(crate) fn resize_buffer_in(&mut self, ctx: &mut Context) -> Result<(), Box<dyn Error>> {
+		// the swapchain makes new buffers itself, the attached one may still be busy
+		if let (Some(swapchain), Some(size)) = (&self.swapchain, self.buffer_size()) {
+			swapchain.borrow_mut().resize(size);
+			return Ok(());
+		}
+		let (Some(buf), Some((w, h))) = (&self.attached_buf, self.buffer_size()) else {
+			return Ok(());
 		};
+		let mut buf = buf.borrow_mut();
+		if (buf.width, buf.height) == (w, h) {
+			return Ok(());
+		}
+		buf.resize_in(ctx, (w, h))?;
+		// it's a new wl_buffer with undefined contents, the old one is gone
+		ctx.wlmm.send_request(&mut self.wl_attach(buf.id)?)?;
+		drop(buf);
+		self.attach_pending = true;
+		self.dirty = true;
 		Ok(())
 	}
 
@@ -106,6 +351,57 @@ impl Surface {
 		self.ctx.borrow().wlmm.send_request(&mut self.wl_frame(cb.borrow().id)?)?;
 		Ok(cb)
 	}
+
+	// the closure is expected to commit, update_frame commits for it otherwise
+	pub fn set_redraw(
+		&mut self,
+		redraw: impl FnMut(&mut Surface, u32) -> Result<(), Box<dyn Error>> + 'static,
+	) {
+		self.redraw = Some(Box::new(redraw));
+	}
+
+	// redraws on the next frame callback, or right away when none is pending
+	pub fn mark_dirty(&mut self) {
+		self.dirty = true;
+	}
+
+	// call this in the event loop. a frame callback is only asked for when something gets drawn,
+	// and hidden surfaces get none, so nothing is drawn until they show again
+	pub fn update_frame(&mut self) -> Result<(), Box<dyn Error>> {
+		if let Some(cb) = &self.frame_cb {
+			let cb = cb.borrow();
+			if !cb.done {
+				return Ok(());
+			}
+			self.last_frame_time = cb.data.unwrap_or(self.last_frame_time);
+		}
+		self.frame_cb = None;
+		if !self.dirty || self.suspended {
+			return Ok(());
+		}
+		let Some(mut redraw) = self.redraw.take() else {
+			return Ok(());
+		};
+		self.dirty = false;
+		// asked for before the redraw commits, so it comes with that commit
+		self.frame_cb = Some(self.frame()?);
+		self.committed = false;
+		let res = redraw(self, self.last_frame_time);
+		if self.redraw.is_none() {
+			self.redraw = Some(redraw);
+		}
+		// it may never have committed, so don't wait on a callback that won't come
+		if res.is_err() {
+			self.frame_cb = None;
+			self.dirty = true;
+			return res;
+		}
+		// the callback only goes out with a commit, without one frames would stop here
+		if !self.committed {
+			self.commit()?;
+		}
+		Ok(())
+	}
 }
 
 impl WaylandObject for Surface {
@@ -114,7 +410,42 @@ impl WaylandObject for Surface {
 		opcode: super::OpCode,
 		payload: &[u8],
 	) -> Result<Vec<EventAction>, Box<dyn Error>> {
-		todo!()
+		let mut pending = vec![];
+		match opcode {
+			// enter
+			0 => {
+				let output = u32::from_wire(payload)?;
+				if !self.outputs.contains(&output) {
+					self.outputs.push(output);
+				}
+				pending.push(EventAction::DebugMessage(
+					DebugLevel::Verbose,
+					format!("{} {} entered output {}", self.as_str(), self.id, output),
+				));
+				pending.push(EventAction::Event(Event::SurfaceEnter(self.id, output)));
+			}
+			// leave
+			1 => {
+				let output = u32::from_wire(payload)?;
+				self.outputs.retain(|o| *o != output);
+				pending.push(EventAction::Event(Event::SurfaceLeave(self.id, output)));
+			}
+			// preferred_buffer_scale
+			2 => {
+				let scale = i32::from_wire(payload)?;
+				self.preferred_scale = Some(scale);
+				pending.push(EventAction::Event(Event::PreferredBufferScale(self.id, scale)));
+			}
+			// preferred_buffer_transform
+			3 => {
+				let transform = Transform::from_u32(u32::from_wire(payload)?)?;
+				self.preferred_transform = Some(transform);
+				pending
+					.push(EventAction::Event(Event::PreferredBufferTransform(self.id, transform)));
+			}
+			inv => return Err(WaylandError::InvalidOpCode(inv, self.as_str()).boxed()),
+		}
+		Ok(pending)
 	}
 
 	fn as_str(&self) -> &'static str {
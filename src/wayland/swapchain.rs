@@ -0,0 +1,109 @@
+use std::{cell::RefCell, collections::VecDeque, error::Error, rc::Rc};
+
+use crate::wayland::{
+	RcCell, allocator::ShmAllocator, buffer::Buffer, region::Region, shm::PixelFormat,
+	surface::Surface,
+};
+
+// older buffers are just redrawn whole
+const MAX_AGE: usize = 8;
+
+// hands out buffers the compositor isn't reading from.
+// count is how many are kept around, more get made while they are all busy
+pub struct Swapchain {
+	allocator: RcCell<ShmAllocator>,
+	pub size: (i32, i32),
+	pub format: PixelFormat,
+	pub count: usize,
+	buffers: Vec<RcCell<Buffer>>,
+	// damage of the last frames, newest first
+	history: VecDeque<Vec<Region>>,
+}
+
+impl Swapchain {
+	// the surface keeps it and updates its size on resize
+	pub fn new(
+		allocator: RcCell<ShmAllocator>,
+		surface: &mut Surface,
+		size: (i32, i32),
+		format: PixelFormat,
+		count: usize,
+	) -> RcCell<Self> {
+		let swapchain = Rc::new(RefCell::new(Self {
+			allocator,
+			size: surface.buffer_size().unwrap_or(size),
+			format,
+			count,
+			buffers: vec![],
+			history: VecDeque::new(),
+		}));
+		surface.swapchain = Some(swapchain.clone());
+		swapchain
+	}
+
+	// buffers of the old size stick around until the compositor releases them
+	pub fn resize(&mut self, size: (i32, i32)) {
+		self.size = size;
+	}
+
+	// buffers the swapchain lets go of are destroyed, which hands their ranges back to the
+	// allocator. busy ones wait for their release and go on a later call
+	pub fn next_buffer(&mut self) -> Result<RcCell<Buffer>, Box<dyn Error>> {
+		let size = self.size;
+		for ix in (0..self.buffers.len()).rev() {
+			let stale = {
+				let buf = self.buffers[ix].borrow();
+				!buf.in_use && (buf.width, buf.height) != size
+			};
+			if stale {
+				self.buffers.remove(ix).borrow_mut().destroy()?;
+			}
+		}
+
+		let free = self.buffers.iter().find(|buf| !buf.borrow().in_use).cloned();
+		let buf = match free {
+			Some(buf) => buf,
+			None => {
+				let buf = Buffer::new_allocated(&self.allocator, size, self.format)?;
+				self.buffers.push(buf.clone());
+				buf
+			}
+		};
+
+		// extras made while everything was busy go once they are free again
+		while self.buffers.len() > self.count {
+			let Some(ix) = self
+				.buffers
+				.iter()
+				.position(|other| !other.borrow().in_use && !Rc::ptr_eq(other, &buf))
+			else {
+				break;
+			};
+			self.buffers.remove(ix).borrow_mut().destroy()?;
+		}
+		Ok(buf)
+	}
+
+	// called by the surface on commit with the damage it sent
+	pub(crate) fn presented(&mut self, shown: &RcCell<Buffer>, damage: &[Region]) {
+		for buf in &self.buffers {
+			let mut buf = buf.borrow_mut();
+			if buf.age > 0 {
+				buf.age += 1;
+			}
+		}
+		shown.borrow_mut().age = 1;
+		self.history.push_front(damage.to_vec());
+		self.history.truncate(MAX_AGE);
+	}
+
+	// what changed since the buffer was last shown, None if all of it has to be redrawn.
+	// the damage of the new frame still has to be drawn on top of this
+	pub fn stale(&self, buf: &Buffer) -> Option<Vec<Region>> {
+		let age = buf.age as usize;
+		if age == 0 || age > self.history.len() + 1 {
+			return None;
+		}
+		Some(self.history.iter().take(age - 1).flatten().copied().collect())
+	}
+}
@@ -0,0 +1,159 @@
+use std::{cell::RefCell, error::Error, rc::Rc};
+
+use crate::wayland::{
+	CtxType, EventAction, RcCell, WaylandError, WaylandObject, WaylandObjectKind,
+	registry::Registry,
+	surface::Surface,
+	wire::{Fixed, Id, WireArgument, WireRequest},
+};
+
+pub struct Viewporter {
+	pub id: Id,
+	ctx: CtxType,
+}
+
+impl Viewporter {
+	pub fn new_bound(registry: &mut Registry) -> Result<RcCell<Self>, Box<dyn Error>> {
+		let obj = Rc::new(RefCell::new(Self {
+			id: 0,
+			ctx: registry.ctx.clone(),
+		}));
+		let id = registry
+			.ctx
+			.borrow_mut()
+			.wlim
+			.new_id_registered(WaylandObjectKind::Viewporter, obj.clone());
+		obj.borrow_mut().id = id;
+		registry.bind(id, WaylandObjectKind::Viewporter, 1)?;
+		Ok(obj)
+	}
+
+	pub(crate) fn wl_destroy(&self) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 0,
+			args: vec![],
+		})
+	}
+
+	pub fn destroy(&self) -> Result<(), Box<dyn Error>> {
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_destroy()?)?;
+		self.ctx.borrow_mut().wlim.free_id(self.id)?;
+		Ok(())
+	}
+
+	pub(crate) fn wl_get_viewport(
+		&self,
+		id: Id,
+		surface_id: Id,
+	) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 1,
+			args: vec![WireArgument::NewId(id), WireArgument::Obj(surface_id)],
+		})
+	}
+
+	// the surface keeps the viewport and sets its destination to the logical size on resize
+	pub fn get_viewport(&self, surface: &mut Surface) -> Result<RcCell<Viewport>, Box<dyn Error>> {
+		let obj = Rc::new(RefCell::new(Viewport {
+			id: 0,
+			ctx: self.ctx.clone(),
+			destination: None,
+		}));
+		let id =
+			self.ctx.borrow_mut().wlim.new_id_registered(WaylandObjectKind::Viewport, obj.clone());
+		obj.borrow_mut().id = id;
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_get_viewport(id, surface.id)?)?;
+		surface.viewport = Some(obj.clone());
+		Ok(obj)
+	}
+}
+
+pub struct Viewport {
+	pub id: Id,
+	ctx: CtxType,
+	pub destination: Option<(i32, i32)>,
+}
+
+impl Viewport {
+	pub(crate) fn wl_destroy(&self) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 0,
+			args: vec![],
+		})
+	}
+
+	pub fn destroy(&self) -> Result<(), Box<dyn Error>> {
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_destroy()?)?;
+		self.ctx.borrow_mut().wlim.free_id(self.id)?;
+		Ok(())
+	}
+
+	pub(crate) fn wl_set_source(
+		&self,
+		(x, y, w, h): (f64, f64, f64, f64),
+	) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 1,
+			args: vec![
+				WireArgument::FixedPrecision(Fixed::from_f64(x)),
+				WireArgument::FixedPrecision(Fixed::from_f64(y)),
+				WireArgument::FixedPrecision(Fixed::from_f64(w)),
+				WireArgument::FixedPrecision(Fixed::from_f64(h)),
+			],
+		})
+	}
+
+	// in buffer coordinates, all -1.0 unsets it
+	pub fn set_source(&self, rect: (f64, f64, f64, f64)) -> Result<(), Box<dyn Error>> {
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_set_source(rect)?)
+	}
+
+	pub(crate) fn wl_set_destination(
+		&self,
+		(w, h): (i32, i32),
+	) -> Result<WireRequest, Box<dyn Error>> {
+		Ok(WireRequest {
+			sender_id: self.id,
+			opcode: 2,
+			args: vec![WireArgument::Int(w), WireArgument::Int(h)],
+		})
+	}
+
+	// in surface coordinates, -1 for both unsets it
+	pub fn set_destination(&mut self, size: (i32, i32)) -> Result<(), Box<dyn Error>> {
+		self.destination = Some(size);
+		self.ctx.borrow().wlmm.send_request(&mut self.wl_set_destination(size)?)
+	}
+}
+
+impl WaylandObject for Viewporter {
+	fn handle(
+		&mut self,
+		opcode: super::OpCode,
+		_payload: &[u8],
+	) -> Result<Vec<EventAction>, Box<dyn Error>> {
+		Err(WaylandError::InvalidOpCode(opcode, self.as_str()).boxed())
+	}
+
+	fn as_str(&self) -> &'static str {
+		WaylandObjectKind::Viewporter.as_str()
+	}
+}
+
+impl WaylandObject for Viewport {
+	fn handle(
+		&mut self,
+		opcode: super::OpCode,
+		_payload: &[u8],
+	) -> Result<Vec<EventAction>, Box<dyn Error>> {
+		Err(WaylandError::InvalidOpCode(opcode, self.as_str()).boxed())
+	}
+
+	fn as_str(&self) -> &'static str {
+		WaylandObjectKind::Viewport.as_str()
+	}
+}
@@ -1,19 +1,42 @@
 use std::{
+	cell::RefCell,
 	collections::VecDeque,
 	env,
 	error::Error,
-	io::{IoSlice, Read},
+	io::{IoSlice, IoSliceMut},
 	os::{
 		fd::RawFd,
-		unix::net::{SocketAncillary, UnixStream},
+		unix::net::{AncillaryData, SocketAncillary, UnixStream},
 	},
 	path::PathBuf,
+	rc::Rc,
 };
 
 use crate::wayland::WaylandError;
 
 pub type Id = u32;
 
+// fds that came with events, in order. objects whose events carry one take it from here
+pub(crate) type FdQueue = Rc<RefCell<VecDeque<RawFd>>>;
+
+// wl_fixed, signed 24.8
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Fixed(pub i32);
+
+impl Fixed {
+	pub fn from_f64(val: f64) -> Self {
+		Self((val * 256.0).round() as i32)
+	}
+
+	pub fn from_int(val: i32) -> Self {
+		Self(val << 8)
+	}
+
+	pub fn to_f64(self) -> f64 {
+		self.0 as f64 / 256.0
+	}
+}
+
 #[derive(Debug)]
 pub struct WireRequest {
 	pub sender_id: Id,
@@ -32,8 +55,7 @@ pub struct WireEventRaw {
 pub enum WireArgument {
 	Int(i32),
 	UnInt(u32),
-	// add actual type and helper funs
-	FixedPrecision(u32),
+	FixedPrecision(Fixed),
 	String(String),
 	Obj(u32),
 	NewId(u32),
@@ -60,6 +82,7 @@ pub enum WireArgumentKind {
 pub struct MessageManager {
 	pub sock: UnixStream,
 	pub q: VecDeque<WireEventRaw>,
+	pub(crate) fds: FdQueue,
 }
 
 impl Drop for MessageManager {
@@ -77,11 +100,15 @@ impl MessageManager {
 		let base = env::var("XDG_RUNTIME_DIR")?;
 		let mut base = PathBuf::from(base);
 		base.push(sockname);
-		let sock = UnixStream::connect(base)?;
+		Self::from_socket(UnixStream::connect(base)?)
+	}
+
+	pub fn from_socket(sock: UnixStream) -> Result<Self, Box<dyn Error>> {
 		sock.set_nonblocking(true)?;
 		let wlmm = Self {
 			sock,
 			q: VecDeque::new(),
+			fds: Rc::new(RefCell::new(VecDeque::new())),
 		};
 
 		Ok(wlmm)
@@ -103,7 +130,7 @@ impl MessageManager {
 					let len = x.len() as u32;
 					buf.append(&mut Vec::from(len.to_ne_bytes()));
 					buf.append(x);
-					buf.resize(x.len() - (x.len() % 4) - 4, 0);
+					buf.resize(buf.len().div_ceil(4) * 4, 0);
 				}
 				WireArgument::FileDescriptor(x) => {
 					fds.push(*x as RawFd);
@@ -135,7 +162,9 @@ impl MessageManager {
 
 	fn get_socket_data(&mut self, buf: &mut [u8]) -> Result<Option<usize>, Box<dyn Error>> {
 		let len;
-		match self.sock.read(buf) {
+		let mut ancillary_buf = [0; 128];
+		let mut ancillary = SocketAncillary::new(&mut ancillary_buf);
+		match self.sock.recv_vectored_with_ancillary(&mut [IoSliceMut::new(buf)], &mut ancillary) {
 			Ok(l) => {
 				len = l;
 			}
@@ -146,6 +175,11 @@ impl MessageManager {
 				}
 			},
 		}
+		for msg in ancillary.messages() {
+			if let Ok(AncillaryData::ScmRights(fds)) = msg {
+				self.fds.borrow_mut().extend(fds);
+			}
+		}
 		Ok(Some(len))
 	}
 
@@ -208,7 +242,7 @@ impl WireArgument {
 		match self {
 			WireArgument::Int(x) => Vec::from(x.to_ne_bytes()),
 			WireArgument::UnInt(x) => Vec::from(x.to_ne_bytes()),
-			WireArgument::FixedPrecision(x) => Vec::from(x.to_ne_bytes()),
+			WireArgument::FixedPrecision(x) => Vec::from(x.0.to_ne_bytes()),
 			WireArgument::String(x) => {
 				let mut complete: Vec<u8> = vec![];
 				// str len + 1 because of nul
@@ -217,8 +251,8 @@ impl WireArgument {
 				complete.append(&mut Vec::from(x.as_str()));
 				// nul
 				complete.push(0);
-				// padding
-				complete.resize(complete.len() - (complete.len() % 4) + 4, 0);
+				// padding, none if nul already ends on a word
+				complete.resize(complete.len().div_ceil(4) * 4, 0);
 				// println!("complete len rn: {}", complete.len());
 				complete
 			}
@@ -251,6 +285,12 @@ impl WireArgument {
 	}
 }
 
+// how many bytes a string argument takes up, length and padding included
+pub(crate) fn wire_str_size(payload: &[u8]) -> Result<usize, Box<dyn Error>> {
+	let len = u32::from_wire(payload)? as usize;
+	Ok(4 + len.div_ceil(4) * 4)
+}
+
 pub trait FromWirePayload: Sized {
 	fn from_wire(payload: &[u8]) -> Result<Self, Box<dyn Error>>;
 }
@@ -283,6 +323,12 @@ impl FromWirePayload for i32 {
 	}
 }
 
+impl FromWirePayload for Fixed {
+	fn from_wire(payload: &[u8]) -> Result<Self, Box<dyn Error>> {
+		Ok(Fixed(i32::from_wire(payload)?))
+	}
+}
+
 // impl FromWirePayload for Vec<u8> {
 // 	fn from_wire(payload: &[u8]) -> Result<Self, Box<dyn Error>> {
 // 		let size = u32::from_wire(payload)? as usize;
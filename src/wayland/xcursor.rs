@@ -0,0 +1,255 @@
+use std::{
+	collections::HashSet,
+	env,
+	error::Error,
+	fs,
+	path::{Path, PathBuf},
+};
+
+use crate::wayland::WaylandError;
+
+const XCURSOR_MAGIC: u32 = 0x72756358;
+const XCURSOR_IMAGE_TYPE: u32 = 0xfffd0002;
+const XCURSOR_IMAGE_MAX_SIZE: u32 = 0x7fff;
+// same as libXcursor
+const XCURSOR_DEFAULT_PATH: &str =
+	"~/.local/share/icons:~/.icons:/usr/share/icons:/usr/share/pixmaps:/usr/X11R6/lib/X11/icons";
+
+#[derive(Debug, Clone)]
+pub struct CursorImage {
+	pub width: u32,
+	pub height: u32,
+	pub xhot: u32,
+	pub yhot: u32,
+	// ms
+	pub delay: u32,
+	// premultiplied argb, same layout as wl_shm argb8888
+	pub pixels: Vec<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Cursor {
+	pub name: String,
+	pub images: Vec<CursorImage>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CursorTheme {
+	pub name: String,
+	pub size: u32,
+	search_path: Vec<PathBuf>,
+}
+
+fn read_u32(data: &[u8], at: usize) -> Result<u32, WaylandError> {
+	data.get(at..at + 4)
+		.map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+		.ok_or(WaylandError::InvalidXcursor)
+}
+
+// returns every frame of the nominal size closest to the requested one
+pub fn parse_xcursor(data: &[u8], size: u32) -> Result<Vec<CursorImage>, WaylandError> {
+	if read_u32(data, 0)? != XCURSOR_MAGIC {
+		return Err(WaylandError::InvalidXcursor);
+	}
+	let header_len = read_u32(data, 4)? as usize;
+	let ntoc = read_u32(data, 12)? as usize;
+
+	let mut toc = vec![];
+	for ix in 0..ntoc {
+		let entry = header_len + ix * 12;
+		let kind = read_u32(data, entry)?;
+		let subtype = read_u32(data, entry + 4)?;
+		let position = read_u32(data, entry + 8)? as usize;
+		if kind == XCURSOR_IMAGE_TYPE {
+			toc.push((subtype, position));
+		}
+	}
+
+	let best = toc
+		.iter()
+		.map(|(nominal, _)| *nominal)
+		.min_by_key(|nominal| nominal.abs_diff(size))
+		.ok_or(WaylandError::InvalidXcursor)?;
+
+	toc.iter()
+		.filter(|(nominal, _)| *nominal == best)
+		.map(|(_, position)| parse_image(data, *position))
+		.collect()
+}
+
+fn parse_image(data: &[u8], position: usize) -> Result<CursorImage, WaylandError> {
+	// chunk header: header len, type, subtype, version
+	if read_u32(data, position + 4)? != XCURSOR_IMAGE_TYPE {
+		return Err(WaylandError::InvalidXcursor);
+	}
+	let width = read_u32(data, position + 16)?;
+	let height = read_u32(data, position + 20)?;
+	if width > XCURSOR_IMAGE_MAX_SIZE || height > XCURSOR_IMAGE_MAX_SIZE {
+		return Err(WaylandError::InvalidXcursor);
+	}
+	let xhot = read_u32(data, position + 24)?;
+	let yhot = read_u32(data, position + 28)?;
+	if xhot > width || yhot > height {
+		return Err(WaylandError::InvalidXcursor);
+	}
+	let delay = read_u32(data, position + 32)?;
+
+	let start = position + 36;
+	let len = (width * height) as usize * 4;
+	let pixels = data
+		.get(start..start + len)
+		.ok_or(WaylandError::InvalidXcursor)?
+		.chunks_exact(4)
+		.map(|px| u32::from_le_bytes([px[0], px[1], px[2], px[3]]))
+		.collect();
+
+	Ok(CursorImage {
+		width,
+		height,
+		xhot,
+		yhot,
+		delay,
+		pixels,
+	})
+}
+
+impl CursorTheme {
+	pub fn new(name: &str, size: u32) -> Self {
+		let path = env::var("XCURSOR_PATH").unwrap_or(String::from(XCURSOR_DEFAULT_PATH));
+		let home = env::var("HOME").ok();
+		let search_path = path
+			.split(':')
+			.filter(|dir| !dir.is_empty())
+			.filter_map(|dir| match dir.strip_prefix("~/") {
+				Some(rest) => home.as_ref().map(|home| Path::new(home).join(rest)),
+				None => Some(PathBuf::from(dir)),
+			})
+			.collect();
+		Self {
+			name: String::from(name),
+			size,
+			search_path,
+		}
+	}
+
+	// honors XCURSOR_THEME and XCURSOR_SIZE
+	pub fn from_env() -> Self {
+		let name = env::var("XCURSOR_THEME").unwrap_or(String::from("default"));
+		let size = env::var("XCURSOR_SIZE").ok().and_then(|s| s.parse::<u32>().ok()).unwrap_or(24);
+		Self::new(&name, size)
+	}
+
+	pub fn load_cursor(&self, name: &str) -> Result<Cursor, Box<dyn Error>> {
+		let mut visited = HashSet::new();
+		let path = self
+			.find_in_theme(&self.name, name, &mut visited)
+			.or_else(|| self.find_in_theme("default", name, &mut visited))
+			.ok_or(WaylandError::CursorNotFound)?;
+		let data = fs::read(path)?;
+		Ok(Cursor {
+			name: String::from(name),
+			images: parse_xcursor(&data, self.size)?,
+		})
+	}
+
+	fn find_in_theme(
+		&self,
+		theme: &str,
+		name: &str,
+		visited: &mut HashSet<String>,
+	) -> Option<PathBuf> {
+		if !visited.insert(String::from(theme)) {
+			return None;
+		}
+		let found = self
+			.search_path
+			.iter()
+			.map(|dir| dir.join(theme).join("cursors").join(name))
+			.find(|path| path.is_file());
+		if found.is_some() {
+			return found;
+		}
+		self.inherits(theme).iter().find_map(|parent| self.find_in_theme(parent, name, visited))
+	}
+
+	fn inherits(&self, theme: &str) -> Vec<String> {
+		self.search_path
+			.iter()
+			.filter_map(|dir| fs::read_to_string(dir.join(theme).join("index.theme")).ok())
+			.flat_map(|index| {
+				index
+					.lines()
+					.filter_map(|line| line.trim().strip_prefix("Inherits"))
+					.filter_map(|rest| rest.trim_start().strip_prefix('='))
+					.flat_map(|list| {
+						list.split([',', ';'])
+							.map(|s| String::from(s.trim()))
+							.filter(|s| !s.is_empty())
+							.collect::<Vec<_>>()
+					})
+					.collect::<Vec<_>>()
+			})
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/xcursor/");
+
+	// arrow has one 2x2 image at 16 and two 3x2 frames at 24
+	fn arrow() -> Vec<u8> {
+		fs::read(format!("{DIR}arrow")).unwrap()
+	}
+
+	fn theme(name: &str) -> CursorTheme {
+		CursorTheme {
+			name: String::from(name),
+			size: 24,
+			search_path: vec![PathBuf::from(format!("{DIR}icons"))],
+		}
+	}
+
+	#[test]
+	fn closest_size() {
+		let images = parse_xcursor(&arrow(), 22).unwrap();
+		assert_eq!(images.len(), 2);
+		assert_eq!((images[0].width, images[0].height), (3, 2));
+		assert_eq!((images[0].xhot, images[0].yhot, images[0].delay), (2, 1, 50));
+		assert_eq!(images[1].delay, 70);
+		assert_eq!(images[1].pixels.len(), 6);
+
+		let images = parse_xcursor(&arrow(), 8).unwrap();
+		assert_eq!(images.len(), 1);
+		assert_eq!((images[0].width, images[0].height), (2, 2));
+	}
+
+	#[test]
+	fn malformed() {
+		let data = arrow();
+		assert!(parse_xcursor(&data[..data.len() - 1], 24).is_err());
+		assert!(parse_xcursor(&data[1..], 24).is_err());
+		// hotspot of the first 24 image outside of it
+		let mut data = arrow();
+		let at = u32::from_le_bytes(data[32..36].try_into().unwrap()) as usize;
+		data[at + 24] = 4;
+		assert!(parse_xcursor(&data, 24).is_err());
+	}
+
+	#[test]
+	fn inherited() {
+		// child and parent inherit each other, so a missing cursor must not loop
+		assert_eq!(theme("child").load_cursor("arrow").unwrap().images.len(), 2);
+		assert_eq!(theme("child").load_cursor("text").unwrap().images.len(), 1);
+		assert_eq!(theme("parent").load_cursor("text").unwrap().images.len(), 1);
+		assert!(theme("child").load_cursor("missing").is_err());
+	}
+
+	#[test]
+	fn default_theme() {
+		assert!(theme("child").load_cursor("wait").is_ok());
+		assert!(theme("nonexistent").load_cursor("wait").is_ok());
+	}
+}
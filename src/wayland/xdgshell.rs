@@ -17,6 +17,11 @@ pub struct XdgWmBase {
 
 impl XdgWmBase {
 	pub fn new_bound(registry: &mut Registry) -> Result<RcCell<Self>, Box<dyn Error>> {
+		// the suspended state needs v6
+		let version = registry
+			.does_implement(WaylandObjectKind::XdgWmBase.as_str())
+			.ok_or(WaylandError::NotInRegistry)?
+			.min(6);
 		let obj = Rc::new(RefCell::new(Self {
 			id: 0,
 			ctx: registry.ctx.clone(),
@@ -27,7 +32,7 @@ impl XdgWmBase {
 			.wlim
 			.new_id_registered(WaylandObjectKind::XdgWmBase, obj.clone());
 		obj.borrow_mut().id = id;
-		registry.bind(id, WaylandObjectKind::XdgWmBase, 1)?;
+		registry.bind(id, WaylandObjectKind::XdgWmBase, version)?;
 		Ok(obj)
 	}
 
@@ -86,7 +91,6 @@ impl XdgWmBase {
 		let id = ctx.wlim.new_id_registered(WaylandObjectKind::XdgSurface, xdgs.clone());
 		ctx.wlmm.send_request(&mut self.wl_get_xdg_surface(surf_id, id)?)?;
 		xdgs.borrow_mut().id = id;
-		ctx.xdg_surface = Some(xdgs.clone());
 		Ok(xdgs)
 	}
 }
@@ -126,6 +130,12 @@ pub struct XdgTopLevel {
 	parent: RcCell<XdgSurface>,
 	title: Option<String>,
 	appid: Option<String>,
+	// as of the last configure
+	pub states: Vec<XdgTopLevelStates>,
+	// the most the window should take up, like the output minus panels
+	pub bounds: Option<(i32, i32)>,
+	// what the window manager supports, like window menus or minimizing
+	pub wm_capabilities: Vec<u32>,
 }
 
 impl XdgTopLevel {
@@ -139,6 +149,9 @@ impl XdgTopLevel {
 			parent: xdg_surface.clone(),
 			title: None,
 			appid: None,
+			states: vec![],
+			bounds: None,
+			wm_capabilities: vec![],
 		}));
 		let mut ctx = ctx.borrow_mut();
 		let id = ctx.wlim.new_id_registered(WaylandObjectKind::XdgTopLevel, xdgtl.clone());
@@ -175,8 +188,8 @@ impl XdgTopLevel {
 }
 
 #[repr(u32)]
-#[derive(Debug)]
-enum XdgTopLevelStates {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XdgTopLevelStates {
 	Maximized = 1,
 	Fullscreen,
 	Resizing,
@@ -254,7 +267,10 @@ impl WaylandObject for XdgTopLevel {
 				let states: Vec<XdgTopLevelStates> = Vec::from_wire(&payload[8..])?
 					.iter()
 					.map(|en| {
-						if (*en as usize) < std::mem::variant_count::<XdgTopLevelStates>() {
+						// the variants start at 1
+						if (1..=std::mem::variant_count::<XdgTopLevelStates>())
+							.contains(&(*en as usize))
+						{
 							Ok(unsafe { std::mem::transmute::<u32, XdgTopLevelStates>(*en) })
 						} else {
 							Err(WaylandError::InvalidEnumVariant)
@@ -271,21 +287,33 @@ impl WaylandObject for XdgTopLevel {
 						states
 					),
 				));
+				// 0 means we get to pick, so stick to the last size
+				let mut parent = self.parent.borrow_mut();
 				if w != 0 && h != 0 {
-					pending.push(EventAction::Resize(w, h));
+					parent.w = w;
+					parent.h = h;
 				}
+				let surface_id = {
+					let mut surface = parent.wl_surface.borrow_mut();
+					surface.suspended = states.contains(&XdgTopLevelStates::Suspended);
+					surface.id
+				};
+				self.states = states;
+				pending.push(EventAction::Resize(surface_id, parent.w, parent.h));
 			}
 			// close
 			1 => {
 				todo!()
 			}
-			// configure_bounds
+			// configure_bounds, 0 means unknown
 			2 => {
-				todo!()
+				let w = i32::from_wire(payload)?;
+				let h = i32::from_wire(&payload[4..])?;
+				self.bounds = (w != 0 && h != 0).then_some((w, h));
 			}
 			// wm_capabilities
 			3 => {
-				todo!()
+				self.wm_capabilities = Vec::from_wire(payload)?;
 			}
 			inv => return Err(WaylandError::InvalidOpCode(inv, self.as_str()).boxed()),
 		}